@@ -0,0 +1,175 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use crate::machine::{DecryptingMachine, Strategy};
+use crate::oracle::{Cipher, Oracle, PaddingScheme};
+
+/// Width of a histogram bucket, in queries
+const BUCKET_WIDTH: usize = 32;
+
+/// Length of the longest histogram bar
+const BAR_WIDTH: usize = 50;
+
+/// What the benchmark attacks
+pub struct Config {
+    pub cipher: Cipher,
+    pub padding: PaddingScheme,
+    pub strategy: Strategy,
+    /// Plaintext lengths, in bytes
+    pub lengths: Vec<usize>,
+    /// Number of random plaintexts of every length
+    pub runs: usize,
+    /// Makes keys and plaintexts the same on every benchmark
+    pub seed: Option<u64>,
+}
+
+/// Totals for all runs with the same plaintext length
+pub struct LengthReport {
+    pub length: usize,
+    pub runs: usize,
+    pub queries: usize,
+    /// Decrypted bytes, padding included
+    pub bytes: usize,
+    pub blocks: usize,
+    pub elapsed: Duration,
+}
+
+pub struct Report {
+    pub lengths: Vec<LengthReport>,
+    /// Queries spent on every decrypted byte of every run
+    pub byte_queries: Vec<usize>,
+}
+
+/// Attacks `config.runs` random plaintexts of every length, each one with
+/// a fresh key, and collects the costs
+pub fn run(config: &Config) -> Report {
+    let mut rng = match config.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let mut report = Report {
+        lengths: vec![],
+        byte_queries: vec![],
+    };
+
+    for &length in &config.lengths {
+        let mut totals = LengthReport {
+            length,
+            runs: config.runs,
+            queries: 0,
+            bytes: 0,
+            blocks: 0,
+            elapsed: Duration::ZERO,
+        };
+
+        for _ in 0..config.runs {
+            let key = config.cipher.random_key_from(&mut rng);
+            let iv = config.cipher.random_iv_from(&mut rng);
+            let oracle = Oracle::with_key(config.cipher, key, iv.clone())
+                .expect("random key and iv have the right size")
+                .with_padding(config.padding);
+
+            let mut plaintext = vec![0; length];
+            rng.fill_bytes(&mut plaintext);
+            let ciphertext = oracle.encrypt(&plaintext);
+
+            let mut machine = DecryptingMachine::new(oracle, iv, ciphertext)
+                .with_strategy(config.strategy)
+                .with_padding_scheme(config.padding);
+
+            let start = Instant::now();
+            let decrypted = machine.run_to_completion();
+            totals.elapsed += start.elapsed();
+
+            totals.queries += decrypted.queries;
+            totals.bytes += decrypted.plaintext.len();
+            totals.blocks += decrypted.plaintext.len() / config.cipher.block_size();
+            report
+                .byte_queries
+                .extend(machine.costs.iter().map(|cost| cost.queries));
+        }
+
+        report.lengths.push(totals);
+    }
+
+    report
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>6} {:>14} {:>14} {:>14}",
+            "length", "runs", "queries/byte", "queries/block", "time/run"
+        )?;
+        for totals in &self.lengths {
+            let runs = totals.runs.max(1);
+            writeln!(
+                f,
+                "{:>8} {:>6} {:>14.1} {:>14.1} {:>14}",
+                totals.length,
+                totals.runs,
+                totals.queries as f64 / totals.bytes.max(1) as f64,
+                totals.queries as f64 / totals.blocks.max(1) as f64,
+                format!("{:.2?}", totals.elapsed / runs as u32),
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "queries per byte:")?;
+        let buckets = histogram(&self.byte_queries);
+        let highest = buckets.iter().copied().max().unwrap_or(0).max(1);
+        for (num, count) in buckets.iter().enumerate() {
+            let from = num * BUCKET_WIDTH;
+            let bar = "#".repeat(count * BAR_WIDTH / highest);
+            writeln!(
+                f,
+                "{:>4}..{:<4} {:>8} {bar}",
+                from,
+                from + BUCKET_WIDTH,
+                count
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts values in buckets of `BUCKET_WIDTH`
+fn histogram(values: &[usize]) -> Vec<usize> {
+    let len = values
+        .iter()
+        .map(|v| v / BUCKET_WIDTH + 1)
+        .max()
+        .unwrap_or(0);
+    let mut buckets = vec![0; len];
+    for value in values {
+        buckets[value / BUCKET_WIDTH] += 1;
+    }
+    buckets
+}
+
+#[test]
+fn test_bench() {
+    let config = Config {
+        cipher: Cipher::Aes128,
+        padding: PaddingScheme::Pkcs7,
+        strategy: Strategy::Naive,
+        lengths: vec![1, 17],
+        runs: 2,
+        seed: Some(42),
+    };
+
+    let report = run(&config);
+    assert_eq!(report.lengths.len(), 2);
+    assert_eq!(report.lengths[0].bytes, 2 * 16);
+    assert_eq!(report.lengths[1].blocks, 2 * 2);
+    assert_eq!(report.byte_queries.len(), 2 * 16 + 2 * 32);
+
+    let total: usize = report.lengths.iter().map(|totals| totals.queries).sum();
+    assert_eq!(total, report.byte_queries.iter().sum::<usize>());
+    assert_eq!(histogram(&report.byte_queries).iter().sum::<usize>(), 96);
+}
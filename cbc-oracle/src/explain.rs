@@ -0,0 +1,206 @@
+use crate::machine::{DecryptingMachine, State};
+use crate::oracle::PaddingOracle;
+
+/// Explains what the machine is going to do next, and why, with the actual
+/// bytes. `D(C)` is the cipherblock decrypted with the key, before it's
+/// xored with anything. Only the oracle can compute it.
+pub fn explain<O: PaddingOracle>(machine: &DecryptingMachine<O>) -> Vec<String> {
+    let block_size = machine.block_size;
+    // The padding is one byte longer than the block, when the block is done
+    let idx = block_size.checked_sub(machine.padding as usize);
+    let block = machine.block_num.map(|num| num + 1).unwrap_or(0);
+
+    match (machine.state, idx) {
+        (State::Start, _) => vec![
+            "The machine knows the iv and the ciphertext, but not the key.".into(),
+            "The oracle only answers whether a ciphertext has a valid padding.".into(),
+            "That's enough to decrypt everything, one byte at a time.".into(),
+        ],
+        (State::ReloadingBlock, _) => explain_reloading_block(machine, block),
+        (State::ReadyToIterate, Some(idx)) => {
+            let target = machine.target_padding();
+            vec![
+                format!("Next: find byte {idx} of block {block}."),
+                "The oracle gets `counter || cipherblock`, so it xors D(C) with our counter \
+                 instead of the real iv."
+                    .into(),
+                format!(
+                    "The padding is valid if the end of D(C) ^ counter is `{}`.",
+                    hex::encode(target)
+                ),
+                format!(
+                    "The bytes after {idx} are already set, so only counter[{idx}] \
+                     is changed until the oracle says yes."
+                ),
+            ]
+        }
+        (State::IteratingByte, Some(idx)) => explain_iterating(machine, idx),
+        (State::FoundByte, Some(idx)) => {
+            let counter = machine.counter[idx];
+            let target = machine.target_padding()[0];
+            let mut lines = vec![
+                format!("The oracle accepted counter[{idx}] = {counter:02x}."),
+                format!(
+                    "So D(C)[{idx}] ^ {counter:02x} = {target:02x}(padding), \
+                     and D(C)[{idx}] = {:02x}.",
+                    counter ^ target
+                ),
+            ];
+            if machine.double_check == Some(true) {
+                lines.extend(explain_double_check(idx, counter, target, true));
+            }
+            lines
+        }
+        (State::CalculatingPlainByte, Some(idx)) => {
+            let (iv, counter) = (machine.iv[idx], machine.counter[idx]);
+            let target = machine.target_padding()[0];
+            vec![
+                "In CBC the plaintext is D(C) ^ iv, and we've just found D(C) = counter ^ padding."
+                    .into(),
+                format!(
+                    "plain = iv ^ counter ^ padding = {iv:02x} ^ {counter:02x} ^ {target:02x} = {:02x}",
+                    iv ^ counter ^ target
+                ),
+            ]
+        }
+        (State::ReloadingCounter, Some(0)) => vec![
+            format!("Every byte of block {block} is known now."),
+            format!("known = {}", hex::encode(&machine.known)),
+        ],
+        (State::ReloadingCounter, Some(idx)) => {
+            let len = machine.padding as usize + 1;
+            let pattern = machine.scheme.pattern(len);
+            let last = block_size - 1;
+            let (known, iv) = (machine.known[machine.known.len() - 1], machine.iv[last]);
+            let padding = pattern[len - 1];
+            vec![
+                format!(
+                    "Next: look for byte {} with the padding `{}`.",
+                    idx - 1,
+                    hex::encode(&pattern)
+                ),
+                "Known bytes are set, so they decrypt to the rest of the padding:".into(),
+                "counter[j] = known[j] ^ padding[j] ^ iv[j], for example".into(),
+                format!(
+                    "counter[{last}] = {known:02x} ^ {padding:02x} ^ {iv:02x} = {:02x}",
+                    known ^ padding ^ iv
+                ),
+            ]
+        }
+        (State::Finished, _) => vec![
+            format!(
+                "Decrypted {} bytes with {} queries, the key was never used.",
+                machine.decrypted.len(),
+                machine.oracle.counter()
+            ),
+            "The padding is recovered too, since it's a part of the plaintext.".into(),
+        ],
+        (State::Failed, _) => vec![
+            format!(
+                "All 256 values of counter[{}] were rejected.",
+                idx.unwrap_or(0)
+            ),
+            "One of them should give a valid padding, so the oracle doesn't leak it: \
+             for example a MAC is checked first, or the padding is not checked at all."
+                .into(),
+        ],
+        _ => vec![],
+    }
+}
+
+fn explain_reloading_block<O: PaddingOracle>(
+    machine: &DecryptingMachine<O>,
+    block: usize,
+) -> Vec<String> {
+    let blocks = machine.ciphertext.len() / machine.block_size;
+    if machine.block_num.is_none() {
+        return vec![
+            "Next: load the first cipherblock.".into(),
+            "Its plaintext is D(C1) ^ iv.".into(),
+        ];
+    }
+
+    let mut lines = vec![format!(
+        "Block {block} is decrypted: {}",
+        hex::encode(&machine.known)
+    )];
+    if block == blocks {
+        lines.push("It was the last block.".into());
+    } else {
+        lines.push(format!("Next: load block {}.", block + 1));
+        lines.push(
+            "In CBC P[i] = D(C[i]) ^ C[i-1], so the current cipherblock becomes the iv.".into(),
+        );
+    }
+    lines
+}
+
+fn explain_iterating<O: PaddingOracle>(machine: &DecryptingMachine<O>, idx: usize) -> Vec<String> {
+    let counter = machine.counter[idx];
+    let target = machine.target_padding()[0];
+    match (machine.ok, machine.double_check) {
+        (Some(false), Some(false)) => {
+            let mut lines = vec![format!(
+                "The oracle accepted counter[{idx}] = {counter:02x}, but rejected the second query."
+            )];
+            lines.extend(explain_double_check(idx, counter, target, false));
+            lines
+        }
+        (Some(false), _) => vec![
+            format!(
+                "counter[{idx}] = {counter:02x} was rejected: D(C)[{idx}] ^ {counter:02x} \
+                 is not {target:02x}(padding)."
+            ),
+            format!(
+                "Trying the next value, {} of 256 tried so far.",
+                machine.attempt
+            ),
+        ],
+        _ => vec![format!(
+            "Trying values of counter[{idx}], until D(C)[{idx}] ^ counter[{idx}] = {target:02x}."
+        )],
+    }
+}
+
+/// Why the first byte of a block is asked twice
+fn explain_double_check(idx: usize, counter: u8, target: u8, ok: bool) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "A longer padding may be valid too, not only the one from {target:02x}: \
+             for PKCS#7 D(C) ^ counter may end with 02 02, for ISO 7816-4 with 80 00."
+        ),
+        format!(
+            "To tell, the oracle was asked again with every byte before counter[{idx}] \
+             flipped. The padding from byte {idx} doesn't depend on them, longer paddings do."
+        ),
+    ];
+    if ok {
+        lines.push(format!(
+            "It was still valid, so counter[{idx}] = {counter:02x} really gives {target:02x}."
+        ));
+    } else {
+        lines.push("It became invalid, so the hit was a longer padding and is skipped.".into());
+    }
+    lines
+}
+
+#[test]
+fn test_explain() {
+    use crate::machine::{test_machine, Strategy};
+    use crate::oracle::{Cipher, PaddingScheme};
+
+    for scheme in [PaddingScheme::Pkcs7, PaddingScheme::Iso7816] {
+        let mut machine = test_machine(b"Hello Internet people", scheme, Cipher::Aes128)
+            .with_strategy(Strategy::Frequency);
+
+        let mut double_checked = false;
+        while !machine.is_done() {
+            let lines = explain(&machine);
+            assert!(!lines.is_empty(), "{:?}", machine.state);
+            double_checked |= lines.iter().any(|line| line.contains("doesn't depend"));
+            machine.advance();
+        }
+        assert!(double_checked);
+        assert!(explain(&machine)[0].starts_with("Decrypted 32 bytes"));
+    }
+}
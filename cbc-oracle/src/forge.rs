@@ -0,0 +1,220 @@
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use crate::machine::{DecryptingMachine, State};
+use crate::oracle::{Oracle, PaddingOracle, PaddingScheme};
+
+/// Decrypts the forged iv and ciphertext with the key. The attacker doesn't
+/// have one, it is used only to show that the forgery works.
+pub type Verifier = Box<dyn Fn(&[u8], &[u8]) -> Option<Vec<u8>>>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ForgeState {
+    /// Starting state
+    Start,
+    /// Picking a random block, that will be the last block of the ciphertext
+    PickingLastBlock,
+    /// Decrypting the current block with the padding oracle, as if its iv was all zeroes
+    DecryptingBlock,
+    /// Xoring the decrypted block with the wanted plaintext to get the previous block
+    ForgingPreviousBlock,
+    /// Decrypting the forgery with the key to check it
+    Verifying,
+    /// Finished forging
+    Finished,
+    /// The decrypting machine failed, so there is nothing to forge
+    Failed,
+}
+
+impl std::fmt::Debug for ForgeState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Start => write!(f, "Start"),
+            Self::PickingLastBlock => write!(f, "Picking the last block"),
+            Self::DecryptingBlock => write!(f, "Decrypting block"),
+            Self::ForgingPreviousBlock => write!(f, "Forging previous block"),
+            Self::Verifying => write!(f, "Verifying"),
+            Self::Finished => write!(f, "Finished"),
+            Self::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+/// ForgingMachine uses the padding oracle to encrypt any plaintext without
+/// the key (so called "CBC-R").
+///
+/// It starts with a random last block `C[n]`. Decrypting it with the oracle
+/// gives `D(C[n])`, and since in CBC `P[n] = D(C[n]) ^ C[n-1]`, choosing
+/// `C[n-1] = D(C[n]) ^ P[n]` makes the last block decrypt to our plaintext.
+/// Then the same is done for `C[n-1]`, and so on, until the iv.
+pub struct ForgingMachine<O = Oracle> {
+    /// Plaintext that the forged ciphertext should decrypt to
+    pub message: Vec<u8>,
+    /// The same plaintext, padded
+    pub plaintext: Vec<u8>,
+    /// Forged blocks, the first one is the current block. When the machine is
+    /// finished, the first block is the iv
+    pub forged: Vec<u8>,
+    /// Index of the plaintext block that we are forging
+    pub block_num: Option<usize>,
+    /// Decryption of the current block, before it's xored with the previous one
+    pub intermediate: Vec<u8>,
+    /// Machine, that decrypts the current block with the padding oracle
+    pub decrypting: DecryptingMachine<O>,
+    /// What the forgery actually decrypts to. Set when verified
+    pub verified: Option<Option<Vec<u8>>>,
+    verifier: Option<Verifier>,
+    /// Picks the random last block
+    rng: StdRng,
+
+    /// The state of the machine
+    pub state: ForgeState,
+}
+
+impl<O: PaddingOracle> ForgingMachine<O> {
+    pub fn new(oracle: O, block_size: usize, message: Vec<u8>) -> Self {
+        let plaintext = PaddingScheme::Pkcs7.pad(&message, block_size);
+        let zeroes = vec![0; block_size];
+        Self {
+            message,
+            plaintext,
+            forged: vec![],
+            block_num: None,
+            intermediate: vec![],
+            decrypting: DecryptingMachine::new(oracle, zeroes.clone(), zeroes),
+            verified: None,
+            verifier: None,
+            rng: StdRng::from_entropy(),
+            state: ForgeState::Start,
+        }
+    }
+
+    /// Checks the result with the key at the end
+    pub fn with_verifier(mut self, verifier: Verifier) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Forges a message for an oracle, that checks another padding scheme
+    pub fn with_padding_scheme(mut self, scheme: PaddingScheme) -> Self {
+        self.plaintext = scheme.pad(&self.message, self.block_size());
+        self.decrypting = self.decrypting.with_padding_scheme(scheme);
+        self
+    }
+
+    /// Picks the same last block, and so forges the same ciphertext, on every run
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.decrypting.block_size
+    }
+
+    /// Returns the forged iv and ciphertext, when the machine has finished
+    pub fn forgery(&self) -> Option<(&[u8], &[u8])> {
+        if matches!(self.state, ForgeState::Verifying | ForgeState::Finished) {
+            Some(self.forged.split_at(self.block_size()))
+        } else {
+            None
+        }
+    }
+
+    /// Starts decrypting the current block
+    fn reload_block(&mut self) {
+        let block = self.forged[..self.block_size()].to_vec();
+        let zeroes = vec![0; self.block_size()];
+        self.decrypting.restart(zeroes, block);
+        self.intermediate.clear();
+    }
+
+    /// Makes the previous block, so the current one decrypts to the plaintext
+    fn forge_previous_block(&mut self) {
+        let block_size = self.block_size();
+        let num = self.block_num.expect("should be set");
+        let plainblock = &self.plaintext[num * block_size..(num + 1) * block_size];
+        let previous = self
+            .intermediate
+            .iter()
+            .zip(plainblock)
+            .map(|(i, p)| i ^ p)
+            .collect::<Vec<_>>();
+        self.forged.splice(0..0, previous);
+    }
+
+    /// Advances the state machine. Most of the time it advances the
+    /// decrypting machine.
+    pub fn advance(&mut self) {
+        self.state = match self.state {
+            ForgeState::Start => ForgeState::PickingLastBlock,
+            ForgeState::PickingLastBlock => {
+                self.forged = vec![0; self.block_size()];
+                self.rng.fill_bytes(&mut self.forged);
+                self.block_num = Some(self.plaintext.len() / self.block_size() - 1);
+                self.reload_block();
+                ForgeState::DecryptingBlock
+            }
+            ForgeState::DecryptingBlock => {
+                self.decrypting.advance();
+                match self.decrypting.state {
+                    State::Finished => {
+                        // The iv was all zeroes, so decrypted text is the
+                        // intermediate state itself
+                        self.intermediate = self.decrypting.decrypted.clone();
+                        ForgeState::ForgingPreviousBlock
+                    }
+                    State::Failed => ForgeState::Failed,
+                    _ => ForgeState::DecryptingBlock,
+                }
+            }
+            ForgeState::ForgingPreviousBlock => {
+                self.forge_previous_block();
+                match self.block_num {
+                    Some(0) if self.verifier.is_some() => ForgeState::Verifying,
+                    Some(0) => ForgeState::Finished,
+                    Some(num) => {
+                        self.block_num = Some(num - 1);
+                        self.reload_block();
+                        ForgeState::DecryptingBlock
+                    }
+                    None => unreachable!("block is set when picking the last block"),
+                }
+            }
+            ForgeState::Verifying => {
+                self.verified = match (&self.verifier, self.forgery()) {
+                    (Some(verifier), Some((iv, ciphertext))) => Some(verifier(iv, ciphertext)),
+                    _ => None,
+                };
+                ForgeState::Finished
+            }
+            ForgeState::Finished => ForgeState::Finished,
+            ForgeState::Failed => ForgeState::Failed,
+        };
+    }
+
+    /// Advances the machine until it finishes forging
+    pub fn run_to_completion(&mut self) {
+        while !matches!(self.state, ForgeState::Finished | ForgeState::Failed) {
+            self.advance();
+        }
+    }
+}
+
+#[test]
+fn test_forge() {
+    use std::sync::Arc;
+
+    let oracle = Arc::new(Oracle::default());
+    let message = b"Forged without the key".to_vec();
+
+    let verifier = oracle.clone();
+    let mut machine = ForgingMachine::new(oracle.clone(), 16, message.clone()).with_verifier(
+        Box::new(move |iv, ciphertext| verifier.decrypt(iv, ciphertext)),
+    );
+    machine.run_to_completion();
+
+    let (iv, ciphertext) = machine.forgery().expect("finished");
+    assert_eq!(ciphertext.len(), 32);
+    assert_eq!(oracle.decrypt(iv, ciphertext), Some(message.clone()));
+    assert_eq!(machine.verified, Some(Some(message)));
+}
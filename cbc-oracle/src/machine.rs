@@ -1,62 +1,42 @@
-use aes::cipher::KeyIvInit;
-use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut};
+use serde::{Deserialize, Serialize};
 
-use rand::Rng;
-use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(test)]
+use crate::oracle::Cipher;
+use crate::oracle::{Oracle, PaddingOracle, PaddingScheme};
 
-type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
-type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+/// The most frequent bytes of English text, from the most frequent one
+const FREQUENT_BYTES: &[u8] =
+    b" etaoinsrhldcumfpgwybvkxjqzETAOINSRHLDCUMFPGWYBVKXJQZ.,'\"-?!:;0123456789()\n";
 
-pub const BLOCK_SIZE: usize = 16;
+/// Flip for the bytes before a hit, that tells the padding we want from a
+/// longer one. A longer PKCS#7 padding needs them to repeat its length, ANSI
+/// X.923 needs zeroes, and ISO 7816-4 needs zeroes after the `80` marker, so
+/// a flipped bit breaks any of them.
+const PERTURBATION: u8 = 0x01;
 
-/// An Oracle is a `black box` which can only tell whether some ciphertext
-/// is a valid cbc or not.
-pub struct Oracle {
-    key: [u8; 16],
-    iv: [u8; 16],
-
-    counter: AtomicUsize,
+/// Order, in which the machine guesses plaintext bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Just increments the counter byte, starting from 0
+    Naive,
+    /// Tries the most frequent bytes of English text first
+    Frequency,
+    /// Tries padding bytes first in the last block, then the same as `Frequency`
+    Padding,
 }
 
-impl Default for Oracle {
-    fn default() -> Self {
-        Self::new()
-    }
+/// How many queries it took to find a plaintext byte
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ByteCost {
+    /// Queries, that the machine has made
+    pub queries: usize,
+    /// Queries, that the naive strategy would make
+    pub naive_queries: usize,
 }
 
-impl Oracle {
-    pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
-        Self {
-            key: rng.gen(),
-            iv: rng.gen(),
-            counter: AtomicUsize::new(0),
-        }
-    }
-
-    pub fn iv(&self) -> [u8; 16] {
-        self.iv
-    }
-
-    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
-        Aes128CbcEnc::new(&self.key.into(), &self.iv.into()).encrypt_padded_vec_mut::<Pkcs7>(data)
-    }
-
-    /// Returns true if decryption is ok (which means padding is ok)
-    pub fn query_decryption(&self, ciphertext: &[u8]) -> bool {
-        self.counter.fetch_add(1, Ordering::Relaxed);
-        Aes128CbcDec::new(&self.key.into(), &self.iv.into())
-            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
-            .is_ok()
-    }
-
-    /// Returns the number of queries
-    pub fn counter(&self) -> usize {
-        self.counter.load(Ordering::Relaxed)
-    }
-}
-
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum State {
     /// Starting state
     Start,
@@ -74,6 +54,8 @@ pub enum State {
     ReloadingBlock,
     /// Finished decryption
     Finished,
+    /// No guess gave a valid padding, so the machine can't go on
+    Failed,
 }
 
 impl std::fmt::Debug for State {
@@ -87,28 +69,73 @@ impl std::fmt::Debug for State {
             Self::ReloadingCounter => write!(f, "Reloading counter"),
             Self::ReloadingBlock => write!(f, "Reloading block"),
             Self::Finished => write!(f, "Finished"),
+            Self::Failed => write!(f, "Failed"),
         }
     }
 }
 
+/// Everything that changes while the machine advances, so it can be
+/// recorded and restored later
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(with = "hex::serde")]
+    pub block: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub iv: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub known: Vec<u8>,
+    pub padding: u8,
+    #[serde(with = "hex::serde")]
+    pub counter: Vec<u8>,
+    pub ok: Option<bool>,
+    #[serde(default)]
+    pub double_check: Option<bool>,
+    pub attempt: usize,
+    pub byte_queries: usize,
+    pub block_num: Option<usize>,
+    #[serde(with = "hex::serde")]
+    pub decrypted: Vec<u8>,
+    pub state: State,
+    /// Number of queries to the oracle at this moment
+    pub queries: usize,
+}
+
 /// DecryptingMachine is a state machine, which if given oracle and ciphertext,
 /// will be able to decrypt it without the key.
-pub struct DecryptingMachine {
+pub struct DecryptingMachine<O = Oracle> {
     /// Current block that we are decrypting
     pub block: Vec<u8>,
     /// iv or previous block, because that's how cbc works
     pub iv: Vec<u8>,
     /// Already decrypted bytes from the current block
     pub known: Vec<u8>,
-    /// Padding we try to hit
+    /// Length of the padding we try to hit
     pub padding: u8,
     /// Our custom crafted block, that we use as iv
     pub counter: Vec<u8>,
     /// If Some(ok), machine is in the `FoundByte` state
     pub ok: Option<bool>,
+    /// Answer to the second query, if the last query was checked twice
+    pub double_check: Option<bool>,
+    /// Order in which we guess plaintext bytes
+    pub strategy: Strategy,
+    /// Padding scheme, that the oracle checks
+    pub scheme: PaddingScheme,
+    /// Plaintext bytes, that we are going to guess for the current byte
+    pub guesses: Vec<u8>,
+    /// Number of guesses we've tried for the current byte
+    pub attempt: usize,
+    /// Cost of every decrypted byte, in the order they were found
+    pub costs: Vec<ByteCost>,
+    /// Queries made for the current byte
+    byte_queries: usize,
 
     /// Our padding oracle. Note that we never use its key directly
-    pub oracle: Oracle,
+    pub oracle: O,
+    /// iv, that was used to encrypt the ciphertext
+    pub initial_iv: Vec<u8>,
+    /// Block size of the cipher. The machine learns it from the iv size
+    pub block_size: usize,
     /// Full ciphertext that we want to decrypt
     pub ciphertext: Vec<u8>,
     /// Index of block that we are decrypting. The block itself is in `self.block`
@@ -120,17 +147,27 @@ pub struct DecryptingMachine {
     pub state: State,
 }
 
-impl DecryptingMachine {
-    pub fn new(oracle: Oracle, ciphertext: Vec<u8>) -> Self {
+impl<O: PaddingOracle> DecryptingMachine<O> {
+    pub fn new(oracle: O, initial_iv: Vec<u8>, ciphertext: Vec<u8>) -> Self {
+        let block_size = initial_iv.len();
         Self {
             block: vec![],
             iv: vec![],
             known: vec![],
             padding: 0x01,
-            counter: vec![0; BLOCK_SIZE],
+            counter: vec![0; block_size],
             ok: None,
+            double_check: None,
+            strategy: Strategy::Naive,
+            scheme: PaddingScheme::Pkcs7,
+            guesses: vec![],
+            attempt: 0,
+            costs: vec![],
+            byte_queries: 0,
 
             oracle,
+            initial_iv,
+            block_size,
             ciphertext,
             block_num: None,
             decrypted: vec![],
@@ -138,35 +175,195 @@ impl DecryptingMachine {
         }
     }
 
-    fn check(&self) -> bool {
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Attacks an oracle, that checks another padding scheme
+    pub fn with_padding_scheme(mut self, scheme: PaddingScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Padding bytes, that the end of the crafted block should decrypt to
+    pub fn target_padding(&self) -> Vec<u8> {
+        self.scheme.pattern(self.padding as usize)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            block: self.block.clone(),
+            iv: self.iv.clone(),
+            known: self.known.clone(),
+            padding: self.padding,
+            counter: self.counter.clone(),
+            ok: self.ok,
+            double_check: self.double_check,
+            attempt: self.attempt,
+            byte_queries: self.byte_queries,
+            block_num: self.block_num,
+            decrypted: self.decrypted.clone(),
+            state: self.state,
+            queries: self.oracle.counter(),
+        }
+    }
+
+    /// Brings the machine back to the snapshot. The oracle is not affected,
+    /// queries that were made stay made.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.block = snapshot.block.clone();
+        self.iv = snapshot.iv.clone();
+        self.known = snapshot.known.clone();
+        self.padding = snapshot.padding;
+        self.counter = snapshot.counter.clone();
+        self.ok = snapshot.ok;
+        self.double_check = snapshot.double_check;
+        self.attempt = snapshot.attempt;
+        self.byte_queries = snapshot.byte_queries;
+        self.block_num = snapshot.block_num;
+        self.decrypted = snapshot.decrypted.clone();
+        self.state = snapshot.state;
+
+        // Both can be derived from the rest, so they are not in the snapshot
+        self.costs.truncate(self.decrypted.len() + self.known.len());
+        if self.block_num.is_some() {
+            self.guesses = self.ordered_guesses();
+        }
+    }
+
+    /// Starts decrypting another ciphertext, keeping the same oracle
+    pub fn restart(&mut self, initial_iv: Vec<u8>, ciphertext: Vec<u8>) {
+        self.block_size = initial_iv.len();
+        self.block.clear();
+        self.iv.clear();
+        self.known.clear();
+        self.padding = 0x01;
+        self.counter = vec![0; self.block_size];
+        self.ok = None;
+        self.double_check = None;
+        self.guesses.clear();
+        self.attempt = 0;
+        self.costs.clear();
+        self.byte_queries = 0;
+
+        self.initial_iv = initial_iv;
+        self.ciphertext = ciphertext;
+        self.block_num = None;
+        self.decrypted.clear();
+        self.state = State::Start;
+    }
+
+    fn check(&mut self) -> bool {
         // Append as a first block, so the rest will not be affected by the iv
-        let zeroes = [0; BLOCK_SIZE];
-        let ciphertext = [&zeroes[..], &self.counter, &self.block].concat();
-        let ok = self.oracle.query_decryption(&ciphertext);
-        // Double check padding when 0x01
-        // This is because when wy try to iterate and hit 0x01,
-        // we may hot any other padding, for example 0x02 0x02.
-        // We don't know for sure, so change the next byte and try again
-        if ok && self.padding == 0x01 {
-            let mut counter = self.counter.clone();
-            // Increase second to last byte, so if it was 0x02 0x02
-            // It would be 0x03 0x02, and the second decryption will fail
-            // But if the padding is 0x01, changing the second to last byte
-            // will not do anything
-            counter[BLOCK_SIZE - 2] = counter[BLOCK_SIZE - 2].wrapping_add(1);
-            let ciphertext = [&zeroes[..], &counter, &self.block].concat();
-            self.oracle.query_decryption(&ciphertext)
-        } else {
-            ok
+        let zeroes = vec![0; self.block_size];
+        let counter = self.counter.clone();
+        let ok = self.query(&zeroes, &counter);
+        self.double_check = None;
+        if !ok || !self.is_ambiguous() {
+            return ok;
+        }
+
+        // The hit may be a longer padding, than the one we try to hit, for
+        // example 02 02 instead of 01 for PKCS#7, 00 02 for ANSI X.923 or
+        // 80 00 instead of 80 for ISO 7816-4. The padding we want doesn't
+        // depend on the bytes before it, a longer one does. So flip all of
+        // them and ask again. The first block is flipped too, since it's
+        // xored with the decrypted counter, and an oracle may look for the
+        // padding beyond the last block. ISO 10126 doesn't check those bytes
+        // at all, so there it can't tell one padding from another
+        let idx = self.block_size - self.padding as usize;
+        let first = vec![PERTURBATION; self.block_size];
+        let mut counter = counter;
+        counter[..idx]
+            .iter_mut()
+            .for_each(|byte| *byte ^= PERTURBATION);
+        let ok = self.query(&first, &counter);
+        self.double_check = Some(ok);
+        ok
+    }
+
+    fn query(&mut self, first: &[u8], counter: &[u8]) -> bool {
+        let ciphertext = [first, counter, &self.block].concat();
+        self.byte_queries += 1;
+        self.oracle.query(&ciphertext)
+    }
+
+    /// Returns true if a hit may be a longer padding, than the one we try
+    /// to hit. For most schemes it happens only at the last byte, when the
+    /// padding length is not fixed yet. ISO 7816-4 has no length byte, so a zero
+    /// may be a part of a longer padding at any position, like `80 00`, even
+    /// at the first byte, if the oracle looks beyond the last block.
+    pub fn is_ambiguous(&self) -> bool {
+        match self.scheme {
+            PaddingScheme::Iso7816 => true,
+            _ => self.padding == 0x01,
+        }
+    }
+
+    /// Orders plaintext bytes, so the most probable ones are tried first
+    fn ordered_guesses(&self) -> Vec<u8> {
+        let last_block = self.ciphertext.len() / self.block_size - 1;
+        let mut preferred = vec![];
+
+        if self.strategy == Strategy::Padding && self.block_num == Some(last_block) {
+            preferred.extend(self.expected_padding());
+        }
+
+        if self.strategy != Strategy::Naive {
+            preferred.extend(FREQUENT_BYTES);
+        }
+
+        let mut guesses = vec![];
+        for guess in preferred.into_iter().chain(0..=u8::MAX) {
+            if !guesses.contains(&guess) {
+                guesses.push(guess);
+            }
+        }
+        guesses
+    }
+
+    /// Bytes, that may continue the padding at the end of the message
+    fn expected_padding(&self) -> Vec<u8> {
+        if self.scheme == PaddingScheme::Iso7816 {
+            // Zeroes continue until the 0x80 marker
+            return if self.known.iter().all(|&b| b == 0) {
+                vec![0x80, 0x00]
+            } else {
+                vec![]
+            };
+        }
+
+        match self.known.last() {
+            // The last byte of the message is always the padding length
+            None => (1..=self.block_size as u8).collect(),
+            // The padding continues until there are as many bytes as its length
+            Some(&len) => {
+                let len = len as usize;
+                if !(1..=self.block_size).contains(&len) || self.known.len() >= len {
+                    return vec![];
+                }
+                let pattern = self.scheme.pattern(len);
+                if pattern.ends_with(&self.known) {
+                    vec![pattern[len - self.known.len() - 1]]
+                } else {
+                    vec![]
+                }
+            }
         }
     }
 
     /// Generates next byte, checks and updates `self.ok` if the padding is ok
     fn advance_iterating(&mut self) -> bool {
-        if self.ok.is_some() {
-            let inc_idx = BLOCK_SIZE - self.padding as usize;
-            self.counter[inc_idx] = self.counter[inc_idx].wrapping_add(1);
-        }
+        let inc_idx = self.block_size - self.padding as usize;
+        let target = self.target_padding()[0];
+        self.counter[inc_idx] = match self.strategy {
+            Strategy::Naive => self.attempt as u8,
+            // Guess is a plaintext byte. To check it, make it look like
+            // a padding after decryption
+            _ => self.guesses[self.attempt % self.guesses.len()] ^ target ^ self.iv[inc_idx],
+        };
+        self.attempt += 1;
         let ok = self.check();
         self.ok = Some(ok);
         ok
@@ -176,13 +373,22 @@ impl DecryptingMachine {
         self.padding = 0x01;
         self.counter.fill(0);
         self.ok = None;
+        self.double_check = None;
     }
 
     /// After hitting the right byte, derives the next plainbyte
     fn calculate_next_plainbyte(&mut self) {
-        let inc_idx = BLOCK_SIZE - self.padding as usize;
-        let plainbyte = self.counter[inc_idx] ^ self.padding ^ self.iv[inc_idx];
+        let inc_idx = self.block_size - self.padding as usize;
+        let target = self.target_padding()[0];
+        let plainbyte = self.counter[inc_idx] ^ target ^ self.iv[inc_idx];
         self.known.insert(0, plainbyte);
+
+        // The naive strategy would try every counter value up to this one
+        let naive_attempts = self.counter[inc_idx] as usize + 1;
+        self.costs.push(ByteCost {
+            queries: self.byte_queries,
+            naive_queries: self.byte_queries - self.attempt + naive_attempts,
+        });
     }
 
     // Relods counter for searching for the next byte
@@ -204,23 +410,26 @@ impl DecryptingMachine {
         // `iv + plaintext`, so to change it to our padding, we should cancel
         // out `iv` and known `plaintext`, and set desired padding.
         // To do this, we just xor the last bytes of our counter with `known`
-        // bytes, `iv` and `padding`.
+        // bytes, `iv` and `padding`. Other schemes only change the padding
+        // bytes, for example `XX 0x00 0x00 0x00 0x05` for ANSI X.923.
+        let target = self.target_padding();
         for (((dst, known), padding), iv) in self
             .counter
             .iter_mut()
             .rev()
             .zip(self.known.iter().rev())
-            .zip(std::iter::repeat(self.padding))
+            .zip(target.into_iter().rev())
             .zip(self.iv.iter().rev())
         {
             *dst = *known ^ padding ^ iv;
         }
         self.ok = None;
+        self.double_check = None;
     }
 
     /// Reloads the next block for decrypting
     fn reload_block(&mut self) -> State {
-        let last_block = self.ciphertext.len() / BLOCK_SIZE - 1;
+        let last_block = self.ciphertext.len() / self.block_size - 1;
         match self.block_num {
             Some(num) if num == last_block => {
                 self.decrypted.append(&mut self.known);
@@ -229,7 +438,7 @@ impl DecryptingMachine {
             Some(num) => {
                 let block = self
                     .ciphertext
-                    .chunks(BLOCK_SIZE)
+                    .chunks(self.block_size)
                     .nth(num + 1)
                     .expect("should be checked");
                 // in cbc the next plainblock is xored with a previous cipherblock
@@ -242,14 +451,13 @@ impl DecryptingMachine {
             }
             // First block is special, because it uses the oracle's iv
             None => {
-                let iv = self.oracle.iv();
                 let block = self
                     .ciphertext
-                    .chunks(BLOCK_SIZE)
+                    .chunks(self.block_size)
                     .next()
                     .expect("there is always at least one block");
                 self.block = block.into();
-                self.iv = iv.into();
+                self.iv = self.initial_iv.clone();
                 self.block_num = Some(0);
                 State::ReadyToIterate
             }
@@ -261,11 +469,22 @@ impl DecryptingMachine {
     pub fn advance(&mut self) {
         self.state = match self.state {
             State::Start => State::ReloadingBlock,
-            State::ReadyToIterate => State::IteratingByte,
+            State::ReadyToIterate => {
+                self.guesses = self.ordered_guesses();
+                self.attempt = 0;
+                self.byte_queries = 0;
+                State::IteratingByte
+            }
             State::IteratingByte => {
                 let ok = self.advance_iterating();
-                if ok {
+                if self.oracle.error().is_some() {
+                    // The oracle didn't answer, its `false` is not a guess
+                    State::Failed
+                } else if ok {
                     State::FoundByte
+                } else if self.attempt > u8::MAX as usize {
+                    // Every byte value was tried, there is no valid padding
+                    State::Failed
                 } else {
                     State::IteratingByte
                 }
@@ -277,7 +496,7 @@ impl DecryptingMachine {
             }
             State::ReloadingCounter => {
                 self.reload_counter();
-                if usize::from(self.padding) == BLOCK_SIZE + 1 {
+                if usize::from(self.padding) == self.block_size + 1 {
                     State::ReloadingBlock
                 } else {
                     State::ReadyToIterate
@@ -288,6 +507,322 @@ impl DecryptingMachine {
                 self.reload_block()
             }
             State::Finished => State::Finished,
+            State::Failed => State::Failed,
         };
     }
+
+    /// Returns true if the machine will not advance anymore
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Finished | State::Failed)
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.state == State::Failed
+    }
+
+    /// Number of queries this machine has made, even if the oracle is shared
+    pub fn queries(&self) -> usize {
+        let found: usize = self.costs.iter().map(|cost| cost.queries).sum();
+        match self.state {
+            // The current byte is not in `costs` yet
+            State::IteratingByte
+            | State::FoundByte
+            | State::CalculatingPlainByte
+            | State::Failed => found + self.byte_queries,
+            _ => found,
+        }
+    }
+
+    /// Advances the machine until it finishes decryption, so the attack can
+    /// be run without any UI. If the machine fails, the plaintext contains
+    /// only the blocks decrypted before that.
+    pub fn run_to_completion(&mut self) -> Decrypted {
+        while !self.is_done() {
+            self.advance();
+        }
+
+        Decrypted {
+            plaintext: self.decrypted.clone(),
+            queries: self.queries(),
+            naive_queries: self.costs.iter().map(|cost| cost.naive_queries).sum(),
+        }
+    }
+}
+
+/// Result of the attack, that was run until the end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decrypted {
+    /// Recovered plaintext. Padding is not stripped, since it's recovered too
+    pub plaintext: Vec<u8>,
+    /// Number of queries the machine needed. The oracle may count more: the
+    /// ones replayed after stepping back, or every sample of a timing probe
+    pub queries: usize,
+    /// Number of queries the naive strategy would need to decrypt the same text
+    pub naive_queries: usize,
+}
+
+/// The machine, that attacks `plaintext` encrypted by the built-in oracle
+#[cfg(test)]
+pub fn test_machine(
+    plaintext: &[u8],
+    scheme: PaddingScheme,
+    cipher: Cipher,
+) -> DecryptingMachine<Oracle> {
+    let oracle = Oracle::new(cipher).with_padding(scheme);
+    let iv = oracle.iv().to_vec();
+    let ciphertext = oracle.encrypt(plaintext);
+    DecryptingMachine::new(oracle, iv, ciphertext).with_padding_scheme(scheme)
+}
+
+#[test]
+fn test_run_to_completion() {
+    let plaintext = b"Hello Internet people";
+    let mut machine = test_machine(plaintext, PaddingScheme::Pkcs7, Cipher::Aes128);
+
+    let decrypted = machine.run_to_completion();
+    assert_eq!(machine.state, State::Finished);
+    assert_eq!(&decrypted.plaintext[..plaintext.len()], plaintext);
+    assert_eq!(decrypted.plaintext[plaintext.len()..], [11; 11]);
+    assert!(decrypted.queries > 0);
+}
+
+/// Queries made before stepping back are not counted twice, so the naive
+/// strategy can be compared with itself
+#[test]
+fn test_queries_after_restore() {
+    let mut machine = test_machine(
+        b"Hello Internet people",
+        PaddingScheme::Pkcs7,
+        Cipher::Aes128,
+    );
+
+    while machine.costs.len() < 3 {
+        machine.advance();
+    }
+    let snapshot = machine.snapshot();
+    while machine.costs.len() < 20 {
+        machine.advance();
+    }
+    machine.restore(&snapshot);
+
+    let decrypted = machine.run_to_completion();
+    assert_eq!(decrypted.queries, decrypted.naive_queries);
+    assert!(decrypted.queries < machine.oracle.counter());
+}
+
+#[test]
+fn test_block_ciphers() {
+    let plaintext = b"Hello Internet people";
+    for cipher in [
+        Cipher::Aes192,
+        Cipher::Aes256,
+        Cipher::Des,
+        Cipher::TripleDes,
+        Cipher::Blowfish,
+    ] {
+        let mut machine = test_machine(plaintext, PaddingScheme::Pkcs7, cipher);
+
+        let decrypted = machine.run_to_completion();
+        let padding = cipher.block_size() - plaintext.len() % cipher.block_size();
+        assert_eq!(&decrypted.plaintext[..plaintext.len()], plaintext);
+        assert_eq!(
+            decrypted.plaintext[plaintext.len()..],
+            vec![padding as u8; padding]
+        );
+    }
+}
+
+#[test]
+fn test_strategies() {
+    let plaintext = b"Guessing frequent letters first is a lot cheaper";
+    let naive = test_machine(plaintext, PaddingScheme::Pkcs7, Cipher::Aes128).run_to_completion();
+
+    for strategy in [Strategy::Frequency, Strategy::Padding] {
+        let mut machine =
+            test_machine(plaintext, PaddingScheme::Pkcs7, Cipher::Aes128).with_strategy(strategy);
+
+        let decrypted = machine.run_to_completion();
+        assert_eq!(decrypted.plaintext, naive.plaintext);
+        assert!(decrypted.queries < decrypted.naive_queries);
+    }
+}
+
+#[test]
+fn test_step_back() {
+    let plaintext = b"Hello Internet";
+    let mut machine = test_machine(plaintext, PaddingScheme::Pkcs7, Cipher::Aes128)
+        .with_strategy(Strategy::Frequency);
+
+    let mut history = vec![];
+    while !machine.is_done() {
+        history.push(machine.snapshot());
+        machine.advance();
+    }
+
+    // Go back to the middle of the block and decrypt the rest once more
+    let middle = history.len() / 2;
+    machine.restore(&history[middle]);
+    assert_eq!(machine.snapshot().known, history[middle].known);
+    assert_eq!(machine.costs.len(), machine.known.len());
+
+    let decrypted = machine.run_to_completion();
+    assert_eq!(&decrypted.plaintext[..plaintext.len()], plaintext);
+}
+
+#[test]
+fn test_padding_schemes() {
+    let plaintext = b"Hello Internet people";
+
+    for scheme in [
+        PaddingScheme::Pkcs7,
+        PaddingScheme::AnsiX923,
+        PaddingScheme::Iso7816,
+    ] {
+        for strategy in [Strategy::Naive, Strategy::Padding] {
+            let mut machine =
+                test_machine(plaintext, scheme, Cipher::Aes128).with_strategy(strategy);
+
+            let decrypted = machine.run_to_completion();
+            assert_eq!(decrypted.plaintext, scheme.pad(plaintext, 16), "{scheme:?}");
+        }
+    }
+
+    // Only the last byte is checked, so the oracle accepts many wrong guesses
+    let mut machine = test_machine(plaintext, PaddingScheme::Iso10126, Cipher::Aes128);
+
+    let decrypted = machine.run_to_completion();
+    assert!(!decrypted.plaintext.starts_with(plaintext));
+}
+
+#[test]
+fn test_encrypt_then_mac() {
+    let oracle = Oracle::default().with_mac(vec![0x42; 32]);
+    let plaintext = b"Hello Internet";
+    let iv = oracle.iv().to_vec();
+    let ciphertext = oracle.encrypt(plaintext);
+    // Only the cipherblocks are attacked, the tag is not one of them
+    assert_eq!(ciphertext.len(), 16);
+    let message = [&ciphertext[..], &oracle.tag(&ciphertext).unwrap()].concat();
+    assert_eq!(oracle.decrypt(&iv, &message), Some(plaintext.to_vec()));
+
+    let mut machine = DecryptingMachine::new(oracle, iv, ciphertext);
+    let decrypted = machine.run_to_completion();
+    assert_eq!(machine.state, State::Failed);
+    assert!(decrypted.plaintext.is_empty());
+    assert_eq!(decrypted.queries, 256);
+}
+
+#[test]
+fn test_random_plaintexts() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Bytes that look like paddings make longer paddings much more likely
+    const TRICKY: [u8; 6] = [0x00, 0x01, 0x02, 0x03, 0x10, 0x80];
+    // Fixed, so a failure is the same on every run
+    const SEEDS: [u64; 3] = [1, 42, 0x5eed];
+
+    for seed in SEEDS {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for scheme in [
+            PaddingScheme::Pkcs7,
+            PaddingScheme::AnsiX923,
+            PaddingScheme::Iso7816,
+        ] {
+            for _ in 0..240 {
+                let cipher = if rng.gen() {
+                    Cipher::Aes128
+                } else {
+                    Cipher::Des
+                };
+                let len = rng.gen_range(0..=cipher.block_size());
+                let plaintext = (0..len)
+                    .map(|_| match rng.gen() {
+                        true => TRICKY[rng.gen_range(0..TRICKY.len())],
+                        false => rng.gen(),
+                    })
+                    .collect::<Vec<u8>>();
+
+                let oracle = Oracle::from_seed(cipher, rng.gen()).with_padding(scheme);
+                let iv = oracle.iv().to_vec();
+                let ciphertext = oracle.encrypt(&plaintext);
+                let mut machine = DecryptingMachine::new(oracle, iv, ciphertext)
+                    .with_strategy(Strategy::Padding)
+                    .with_padding_scheme(scheme);
+
+                let decrypted = machine.run_to_completion();
+                assert_eq!(
+                    decrypted.plaintext,
+                    scheme.pad(&plaintext, cipher.block_size()),
+                    "seed {seed}, {scheme:?}, {cipher:?}, plaintext {}",
+                    hex::encode(&plaintext)
+                );
+            }
+        }
+    }
+}
+
+// Unlike #[test] functions, the macro itself needs a dev-dependency
+#[cfg(test)]
+proptest::proptest! {
+    #![proptest_config(proptest::test_runner::Config::with_cases(48))]
+
+    /// Whole blocks and a tail of any length, so every padding length is hit
+    #[test]
+    fn prop_recovers_padded_plaintext(
+        blocks in 0..=2usize,
+        padding in 1..=16usize,
+        seed: u64,
+        strategy in proptest::sample::select(vec![
+            Strategy::Naive,
+            Strategy::Frequency,
+            Strategy::Padding,
+        ]),
+    ) {
+        use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+        let mut plaintext = vec![0; blocks * 16 + 16 - padding];
+        StdRng::seed_from_u64(seed).fill_bytes(&mut plaintext);
+
+        let oracle = Oracle::from_seed(Cipher::Aes128, seed);
+        let iv = oracle.iv().to_vec();
+        let ciphertext = oracle.encrypt(&plaintext);
+        let mut machine = DecryptingMachine::new(oracle, iv, ciphertext).with_strategy(strategy);
+
+        let decrypted = machine.run_to_completion();
+        proptest::prop_assert_eq!(machine.state, State::Finished);
+        proptest::prop_assert_eq!(&decrypted.plaintext, &PaddingScheme::Pkcs7.pad(&plaintext, 16));
+
+        // Every byte takes one query at least and 256 at most, and the last
+        // byte of a block may have two hits, confirmed with a query each
+        let blocks = blocks + 1;
+        let queries = machine.oracle.counter();
+        proptest::prop_assert_eq!(queries, decrypted.queries);
+        proptest::prop_assert!(queries >= blocks * 16);
+        proptest::prop_assert!(queries <= blocks * (16 * 256 + 2));
+        if strategy == Strategy::Naive {
+            proptest::prop_assert_eq!(queries, decrypted.naive_queries);
+        }
+    }
+
+    /// Restoring any snapshot and running on gives the same plaintext
+    #[test]
+    fn prop_restore_any_snapshot(len in 0..48usize, step: proptest::sample::Index, seed: u64) {
+        let plaintext = vec![b'a'; len];
+        let oracle = Oracle::from_seed(Cipher::Aes128, seed);
+        let iv = oracle.iv().to_vec();
+        let ciphertext = oracle.encrypt(&plaintext);
+        let mut machine =
+            DecryptingMachine::new(oracle, iv, ciphertext).with_strategy(Strategy::Frequency);
+
+        let mut history = vec![];
+        while !machine.is_done() {
+            history.push(machine.snapshot());
+            machine.advance();
+        }
+        let expected = machine.decrypted.clone();
+
+        machine.restore(&history[step.index(history.len())]);
+        let decrypted = machine.run_to_completion();
+        proptest::prop_assert_eq!(decrypted.plaintext, expected);
+    }
 }
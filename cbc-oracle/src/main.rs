@@ -1,18 +1,44 @@
+pub mod bench;
+pub mod explain;
+pub mod forge;
 pub mod machine;
+pub mod net;
+pub mod oracle;
+pub mod parallel;
+pub mod report;
+pub mod timing;
+pub mod trace;
 pub mod ui;
 
+use anyhow::Context;
+use clap::{Parser, Subcommand};
 use crossterm::{
     event::{self, Event, KeyCode},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use machine::{DecryptingMachine, Oracle};
+use forge::{ForgeState, ForgingMachine, Verifier};
+use machine::{DecryptingMachine, Snapshot, Strategy};
+use net::{RemoteOracle, Server};
+use oracle::{Cipher, Oracle, PaddingOracle, PaddingScheme, TAG_SIZE};
+use parallel::ParallelMachine;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use report::Report;
+use timing::{TimingOracle, TimingProbe};
+use trace::{Recording, Replay, TraceWriter};
+use ui::Status;
 
-use std::{error::Error, io, time::Duration};
+use std::{
+    error::Error,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use tui::{
     backend::{Backend, CrosstermBackend},
-    Terminal,
+    Frame, Terminal,
 };
 
 use crate::machine::State;
@@ -20,14 +46,335 @@ use crate::machine::State;
 // Encrypted by the `hex` gang (⌐■_■)
 const SECRET: &str = "546865206d6f737420616d617a696e672073797374656d7320627265616b20696e20746865206d6f737420616d617a696e67207761792e";
 
+/// Where `e` exports the report in the UI, if `--report` is not given
+const DEFAULT_REPORT: &str = "report.md";
+
+#[derive(Debug)]
+struct HexString(Vec<u8>);
+
+impl std::str::FromStr for HexString {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        hex::decode(s).map(Self)
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(about, long_about = None)]
+struct Args {
+    /// Run the attack until the end without the UI and print the result
+    #[clap(long)]
+    headless: bool,
+
+    #[clap(flatten)]
+    target: TargetArgs,
+
+    /// Order in which the machine guesses plaintext bytes
+    #[clap(long, value_enum, default_value = "naive")]
+    strategy: Strategy,
+
+    /// Instead of decrypting, use the oracle to encrypt the given text
+    /// without the key
+    #[clap(long, value_name = "TEXT")]
+    forge: Option<String>,
+
+    /// Attack the oracle, that was started with `serve` on the given address,
+    /// instead of the local one
+    #[clap(
+        long,
+        value_name = "ADDR",
+        conflicts_with_all = &["message", "key", "iv", "cipher", "mac"]
+    )]
+    oracle: Option<String>,
+
+    /// Attack an oracle, that answers the same for any error, but takes
+    /// longer when the padding is valid
+    #[clap(long, conflicts_with = "oracle")]
+    timing: bool,
+
+    /// How many times every query is repeated to tell a slow answer from a fast one
+    #[clap(long, default_value_t = 5, requires = "timing")]
+    samples: usize,
+
+    /// Attack all blocks at the same time, each one in its own thread
+    #[clap(long, conflicts_with_all = &["forge", "record"])]
+    parallel: bool,
+
+    /// Write the recovered plaintext and the cost of the attack to a report:
+    /// Markdown for `.md` files, JSON otherwise. In the UI press `e` to export it
+    #[clap(long, value_name = "PATH", conflicts_with_all = &["forge", "parallel"])]
+    report: Option<PathBuf>,
+
+    /// Write every state of the machine and every query to the oracle
+    /// to a JSON Lines file
+    #[clap(long, value_name = "PATH", conflicts_with = "forge")]
+    record: Option<PathBuf>,
+
+    /// Step through an attack recorded with `--record` instead of running a new one
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = &["headless", "forge", "record", "oracle", "timing", "message", "parallel"]
+    )]
+    replay: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Expose the oracle over HTTP, so it can be attacked over the network
+    Serve {
+        /// Address to listen on
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        #[clap(flatten)]
+        target: TargetArgs,
+    },
+    /// Attack many random plaintexts without the UI and report the costs
+    Bench {
+        /// Plaintext lengths in bytes, comma-separated
+        #[clap(
+            long,
+            value_name = "BYTES",
+            use_value_delimiter = true,
+            default_value = "1,16,64,256"
+        )]
+        lengths: Vec<usize>,
+
+        /// Number of plaintexts of every length
+        #[clap(long, default_value_t = 20)]
+        runs: usize,
+
+        #[clap(long, value_enum, default_value = "aes128")]
+        cipher: Cipher,
+
+        #[clap(long, value_enum, default_value = "pkcs7")]
+        padding: PaddingScheme,
+
+        #[clap(long, value_enum, default_value = "naive")]
+        strategy: Strategy,
+
+        /// Use the same keys and plaintexts on every run
+        #[clap(long, value_name = "NUMBER")]
+        seed: Option<u64>,
+    },
+}
+
+/// The oracle and the message, that the machine attacks
+#[derive(clap::Args, Debug)]
+struct TargetArgs {
+    /// Block cipher, that the oracle uses in CBC mode
+    #[clap(long, value_enum, default_value = "aes128")]
+    cipher: Cipher,
+
+    /// Padding scheme, that the oracle checks. The attacker is assumed to know it
+    #[clap(long, value_enum, default_value = "pkcs7")]
+    padding: PaddingScheme,
+
+    /// Hex-encoded key of the oracle. Random by default
+    #[clap(long, value_name = "HEX")]
+    key: Option<HexString>,
+
+    /// Derive the key and the iv, that were not given, from the seed.
+    /// The same seed gives the same run, up to every query
+    #[clap(long, value_name = "NUMBER")]
+    seed: Option<u64>,
+
+    /// Hex-encoded iv. Random by default
+    #[clap(long, value_name = "HEX")]
+    iv: Option<HexString>,
+
+    /// Text to encrypt and attack instead of the built-in secret
+    #[clap(long, value_name = "TEXT", group = "message")]
+    plaintext: Option<String>,
+
+    /// The same as `--plaintext`, but hex-encoded
+    #[clap(long, value_name = "HEX", group = "message")]
+    plaintext_hex: Option<HexString>,
+
+    /// Read the plaintext to attack from a file
+    #[clap(long, value_name = "PATH", group = "message")]
+    plaintext_file: Option<PathBuf>,
+
+    /// Protect messages with encrypt-then-MAC: the oracle checks an
+    /// HMAC-SHA256 tag before unpadding, and the attack fails
+    #[clap(long, conflicts_with = "ciphertext")]
+    mac: bool,
+
+    /// Hex-encoded ciphertext to decrypt. The oracle has to know the key
+    /// it was encrypted with, so `--key` and `--iv` are required
+    #[clap(
+        long,
+        value_name = "HEX",
+        group = "message",
+        requires_all = &["key", "iv"]
+    )]
+    ciphertext: Option<HexString>,
+}
+
+impl TargetArgs {
+    /// Creates the oracle together with the iv and the ciphertext to decrypt
+    fn build(&self) -> anyhow::Result<(Oracle, Vec<u8>, Vec<u8>)> {
+        let cipher = self.cipher;
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        // The key is drawn first, so the seed gives the same key and iv as `Oracle::from_seed`
+        let key = match &self.key {
+            Some(HexString(key)) => key.clone(),
+            None => cipher.random_key_from(&mut rng),
+        };
+        let iv = match &self.iv {
+            Some(HexString(iv)) => iv.clone(),
+            None => cipher.random_iv_from(&mut rng),
+        };
+        let mut oracle = Oracle::with_key(cipher, key, iv.clone())?.with_padding(self.padding);
+        if self.mac {
+            let mut mac_key = vec![0; TAG_SIZE];
+            rng.fill_bytes(&mut mac_key);
+            oracle = oracle.with_mac(mac_key);
+        }
+
+        if let Some(HexString(ciphertext)) = &self.ciphertext {
+            if ciphertext.is_empty() || ciphertext.len() % cipher.block_size() != 0 {
+                anyhow::bail!(
+                    "ciphertext length should be a multiple of {} bytes",
+                    cipher.block_size()
+                );
+            }
+            return Ok((oracle, iv, ciphertext.clone()));
+        }
+
+        let plaintext = match (&self.plaintext, &self.plaintext_hex, &self.plaintext_file) {
+            (Some(text), _, _) => text.as_bytes().to_vec(),
+            (_, Some(HexString(data)), _) => data.clone(),
+            (_, _, Some(path)) => std::fs::read(path)?,
+            _ => hex::decode(SECRET)?,
+        };
+        let ciphertext = oracle.encrypt(&plaintext);
+
+        Ok((oracle, iv, ciphertext))
+    }
+}
+
+/// Shared between threads in the parallel mode
+type DynOracle = Box<dyn PaddingOracle + Send + Sync>;
+
+type Machine = DecryptingMachine<DynOracle>;
+type Forger = ForgingMachine<DynOracle>;
+type Parallel = ParallelMachine<DynOracle>;
+
+/// The oracle that the machine attacks, together with the intercepted message
+struct Target {
+    oracle: DynOracle,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+    /// Only the local oracle can tell what a forged ciphertext decrypts to
+    verifier: Option<Verifier>,
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    match &args.command {
+        Some(Command::Serve { listen, target }) => {
+            return run_server(listen, target).map_err(Into::into)
+        }
+        Some(Command::Bench {
+            lengths,
+            runs,
+            cipher,
+            padding,
+            strategy,
+            seed,
+        }) => {
+            let config = bench::Config {
+                cipher: *cipher,
+                padding: *padding,
+                strategy: *strategy,
+                lengths: lengths.clone(),
+                runs: *runs,
+                seed: *seed,
+            };
+            print!("{}", bench::run(&config));
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if let Some(path) = &args.replay {
+        let mut replay = Replay::load(path)?;
+        return with_terminal(|terminal| event_loop(terminal, &mut replay));
+    }
+
+    let mut target = new_target(&args)?;
+    let trace = match &args.record {
+        Some(path) => {
+            let trace = TraceWriter::create(path)?;
+            target.oracle = Box::new(Recording::new(target.oracle, trace.clone()));
+            Some(trace)
+        }
+        None => None,
+    };
+
+    if let Some(message) = &args.forge {
+        let mut machine =
+            ForgingMachine::new(target.oracle, target.iv.len(), message.clone().into());
+        if let Some(verifier) = target.verifier {
+            machine = machine.with_verifier(verifier);
+        }
+        if let Some(seed) = args.target.seed {
+            machine = machine.with_seed(seed);
+        }
+        machine = machine.with_padding_scheme(args.target.padding);
+
+        if args.headless {
+            return run_forge_headless(machine).map_err(Into::into);
+        }
+        return with_terminal(|terminal| event_loop(terminal, &mut machine));
+    }
+
+    if args.parallel {
+        let mut machine = ParallelMachine::new(target.oracle, target.iv, target.ciphertext)
+            .with_strategy(args.strategy)
+            .with_padding_scheme(args.target.padding);
+
+        if args.headless {
+            return run_parallel_headless(machine).map_err(Into::into);
+        }
+        return with_terminal(|terminal| event_loop(terminal, &mut machine));
+    }
+
+    let machine = DecryptingMachine::new(target.oracle, target.iv, target.ciphertext)
+        .with_strategy(args.strategy)
+        .with_padding_scheme(args.target.padding);
+
+    if args.headless {
+        return run_headless(machine, trace, args.report.as_deref()).map_err(Into::into);
+    }
+
+    let report = args
+        .report
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_REPORT));
+    with_terminal(|terminal| run_app(terminal, machine, trace, &report))
+}
+
+fn with_terminal(
+    app: impl FnOnce(&mut Terminal<CrosstermBackend<io::Stdout>>) -> anyhow::Result<()>,
+) -> Result<(), Box<dyn Error>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run_app(&mut terminal);
+    let res = app(&mut terminal);
 
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
@@ -36,45 +383,390 @@ fn main() -> Result<(), Box<dyn Error>> {
     res.map_err(Into::into)
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> anyhow::Result<()> {
-    let oracle = Oracle::new();
+fn new_target(args: &Args) -> anyhow::Result<Target> {
+    if let Some(addr) = &args.oracle {
+        let remote = RemoteOracle::connect(addr)?;
+        let (iv, ciphertext) = remote.fetch_ciphertext()?;
+        return Ok(Target {
+            oracle: Box::new(remote),
+            iv,
+            ciphertext,
+            verifier: None,
+        });
+    }
+
+    let (oracle, iv, ciphertext) = args.target.build()?;
+
+    if args.timing {
+        let probe = TimingProbe::new(TimingOracle::new(oracle), args.samples);
+        return Ok(Target {
+            oracle: Box::new(probe),
+            iv,
+            ciphertext,
+            verifier: None,
+        });
+    }
+
+    let oracle = Arc::new(oracle);
+    let verifier = oracle.clone();
+    Ok(Target {
+        oracle: Box::new(oracle),
+        iv,
+        ciphertext,
+        verifier: Some(Box::new(move |iv, ciphertext| {
+            verifier.decrypt(iv, ciphertext)
+        })),
+    })
+}
+
+fn run_server(listen: &str, target: &TargetArgs) -> anyhow::Result<()> {
+    let (oracle, iv, ciphertext) = target.build()?;
+
+    let server = Server::bind(listen, oracle, iv, ciphertext)?;
+    println!("Oracle is listening on http://{}", server.local_addr()?);
+    server.run()
+}
+
+fn run_headless(
+    mut machine: Machine,
+    trace: Option<TraceWriter>,
+    report: Option<&Path>,
+) -> anyhow::Result<()> {
+    if let Some(trace) = &trace {
+        trace.start(&machine)?;
+        while !machine.is_done() {
+            machine.advance();
+            trace.state(&machine)?;
+        }
+        trace.flush()?;
+    }
+    let decrypted = machine.run_to_completion();
+
+    println!("{:?}", String::from_utf8_lossy(&decrypted.plaintext));
+    println!("hex: {}", hex::encode(&decrypted.plaintext));
+    println!("queries: {}", decrypted.queries);
+    // Every sample of the timing probe is a request too
+    if machine.oracle.counter() != decrypted.queries {
+        println!("requests to the oracle: {}", machine.oracle.counter());
+    }
+    if machine.strategy != Strategy::Naive {
+        println!("naive strategy would make: {}", decrypted.naive_queries);
+    }
+    if let Some(path) = report {
+        Report::new(&machine).write(path)?;
+        println!("report: {}", path.display());
+    }
+    if machine.state == State::Failed {
+        return Err(failure(&machine.oracle));
+    }
+
+    Ok(())
+}
+
+fn run_parallel_headless(mut machine: Parallel) -> anyhow::Result<()> {
+    let decrypted = machine.run_to_completion();
+
+    println!("{:?}", String::from_utf8_lossy(&decrypted.plaintext));
+    println!("hex: {}", hex::encode(&decrypted.plaintext));
+    println!("queries: {}", decrypted.queries);
+    for (num, block) in machine.machines.iter().enumerate() {
+        println!("block {}: {} queries", num + 1, block.queries());
+    }
+    if machine.is_failed() {
+        return Err(failure(&machine.oracle));
+    }
+
+    Ok(())
+}
+
+/// Why the attack stopped: the oracle didn't answer, or it rejected everything
+fn failure(oracle: &impl PaddingOracle) -> anyhow::Error {
+    match oracle.error() {
+        Some(err) => anyhow::anyhow!("the oracle didn't answer: {err}"),
+        None => anyhow::anyhow!("the oracle accepted none of the guesses, the attack failed"),
+    }
+}
+
+fn run_forge_headless(mut machine: Forger) -> anyhow::Result<()> {
+    machine.run_to_completion();
+    if let Some(err) = machine.decrypting.oracle.error() {
+        anyhow::bail!("the oracle didn't answer: {err}");
+    }
+
+    let (iv, ciphertext) = machine
+        .forgery()
+        .context("no valid padding found, the oracle can't be used to forge")?;
+    println!("iv: {}", hex::encode(iv));
+    println!("ciphertext: {}", hex::encode(ciphertext));
+    println!("queries: {}", machine.decrypting.oracle.counter());
+
+    match machine.verified {
+        Some(Some(decrypted)) if decrypted == machine.message => {
+            println!(
+                "verified: decrypts to {:?}",
+                String::from_utf8_lossy(&decrypted)
+            )
+        }
+        Some(_) => anyhow::bail!("forgery doesn't decrypt to the plaintext"),
+        None => println!("verified: no key to check"),
+    }
+
+    Ok(())
+}
+
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    machine: Machine,
+    trace: Option<TraceWriter>,
+    report: &Path,
+) -> anyhow::Result<()> {
+    if let Some(trace) = &trace {
+        trace.start(&machine)?;
+    }
+
+    let mut screen = DecryptScreen {
+        machine,
+        trace,
+        report,
+        history: vec![],
+        stepped_back: false,
+        status: None,
+    };
+    event_loop(terminal, &mut screen)?;
+
+    match screen.trace {
+        Some(trace) => trace.flush(),
+        None => Ok(()),
+    }
+}
+
+/// Pace of the UI, the same for every screen
+struct Controls {
+    /// Timeout between redraws
+    timeout: Duration,
+    /// Whether to make a step after the redraw
+    advance: bool,
+    run_without_pause: bool,
+}
+
+/// What a screen of the UI shows and how it steps. Pacing and the keys every
+/// screen has, `q`, `c`, `r` and the arrows, are up to `event_loop`
+trait Screen {
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>, controls: &Controls);
+
+    /// Makes one step, only called if the screen is not done
+    fn advance(&mut self) -> anyhow::Result<()>;
 
-    let ciphertext = oracle.encrypt(&hex::decode(SECRET).expect("should be ok"));
+    fn is_done(&self) -> bool;
 
-    let mut machine = DecryptingMachine::new(oracle, ciphertext);
+    /// Whether to step without waiting for `c`, like while iterating a byte
+    fn keeps_going(&self) -> bool;
 
-    // Timeout between redraws
-    let mut timeout = Duration::from_millis(200);
+    /// Gets every key after the common ones are handled, so a screen can
+    /// add its own
+    fn key(&mut self, _code: KeyCode, _controls: &mut Controls) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
 
+fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    screen: &mut impl Screen,
+) -> anyhow::Result<()> {
     const TIMEOUT_STEP: Duration = Duration::from_millis(10);
 
-    let mut advance = false;
-    let mut run_without_pause = false;
+    let mut controls = Controls {
+        timeout: Duration::from_millis(200),
+        advance: false,
+        run_without_pause: false,
+    };
     loop {
-        terminal.draw(|frame| ui::ui(frame, &machine, timeout, advance))?;
-
-        if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Char('c') => advance = true,
-                    KeyCode::Char('r') => run_without_pause = !run_without_pause,
-                    KeyCode::Left => timeout = timeout.saturating_sub(TIMEOUT_STEP),
-                    KeyCode::Right => timeout = timeout.saturating_add(TIMEOUT_STEP),
-                    _ => {}
+        terminal.draw(|frame| screen.draw(frame, &controls))?;
+
+        if event::poll(controls.timeout)? {
+            match event::read()? {
+                Event::Key(key) => {
+                    match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Char('c') => controls.advance = true,
+                        KeyCode::Char('r') => {
+                            controls.run_without_pause = !controls.run_without_pause
+                        }
+                        KeyCode::Left => {
+                            controls.timeout = controls.timeout.saturating_sub(TIMEOUT_STEP)
+                        }
+                        KeyCode::Right => {
+                            controls.timeout = controls.timeout.saturating_add(TIMEOUT_STEP)
+                        }
+                        _ => {}
+                    }
+                    screen.key(key.code, &mut controls)?;
                 }
+                // Redraw with the new size right away, without a step
+                Event::Resize(..) => continue,
+                _ => {}
             }
         }
 
-        if advance {
-            machine.advance();
+        if controls.advance && !screen.is_done() {
+            screen.advance()?;
         }
 
-        if machine.state == State::Finished {
+        if screen.is_done() {
             // Turn off so it will not redraw endlessly
-            run_without_pause = false;
+            controls.run_without_pause = false;
+        }
+
+        controls.advance = controls.run_without_pause || screen.keeps_going();
+    }
+}
+
+/// Decryption, that can be stepped back, recorded and exported
+struct DecryptScreen<'a> {
+    machine: Machine,
+    trace: Option<TraceWriter>,
+    report: &'a Path,
+    /// States before every advance, so the machine can be stepped back
+    history: Vec<Snapshot>,
+    /// Don't iterate on its own after stepping back, until `c` is pressed
+    stepped_back: bool,
+    /// A failed export shouldn't end the whole session, so it's only shown
+    status: Option<Status>,
+}
+
+impl Screen for DecryptScreen<'_> {
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>, controls: &Controls) {
+        ui::ui(
+            frame,
+            &self.machine,
+            controls.timeout,
+            controls.advance,
+            self.status.as_ref(),
+        )
+    }
+
+    fn advance(&mut self) -> anyhow::Result<()> {
+        self.history.push(self.machine.snapshot());
+        self.machine.advance();
+        if let Some(trace) = &self.trace {
+            trace.state(&self.machine)?;
+        }
+        Ok(())
+    }
+
+    fn is_done(&self) -> bool {
+        self.machine.is_done()
+    }
+
+    fn keeps_going(&self) -> bool {
+        !self.stepped_back && self.machine.state == State::IteratingByte
+    }
+
+    fn key(&mut self, code: KeyCode, controls: &mut Controls) -> anyhow::Result<()> {
+        match code {
+            KeyCode::Char('c') | KeyCode::Char('r') => self.stepped_back = false,
+            KeyCode::Char('b') => {
+                controls.advance = false;
+                controls.run_without_pause = false;
+                self.stepped_back = true;
+                if let Some(snapshot) = self.history.pop() {
+                    self.machine.restore(&snapshot);
+                    if let Some(trace) = &self.trace {
+                        trace.state(&self.machine)?;
+                    }
+                }
+            }
+            KeyCode::Char('e') => {
+                let report = self.report.display();
+                self.status = Some(match Report::new(&self.machine).write(self.report) {
+                    Ok(()) => Status::Info(format!("report written to {report}")),
+                    Err(err) => Status::Error(format!("can't write {report}: {err}")),
+                });
+            }
+            _ => {}
         }
+        Ok(())
+    }
+}
+
+impl Screen for Forger {
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>, controls: &Controls) {
+        ui::forge_ui(frame, self, controls.timeout, controls.advance)
+    }
+
+    fn advance(&mut self) -> anyhow::Result<()> {
+        ForgingMachine::advance(self);
+        Ok(())
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.state, ForgeState::Finished | ForgeState::Failed)
+    }
 
-        advance = run_without_pause || machine.state == State::IteratingByte;
+    fn keeps_going(&self) -> bool {
+        self.state == ForgeState::DecryptingBlock && self.decrypting.state == State::IteratingByte
+    }
+}
+
+impl Screen for Parallel {
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>, controls: &Controls) {
+        ui::parallel_ui(frame, self, controls.timeout, controls.advance)
+    }
+
+    fn advance(&mut self) -> anyhow::Result<()> {
+        ParallelMachine::advance(self);
+        Ok(())
+    }
+
+    fn is_done(&self) -> bool {
+        ParallelMachine::is_done(self)
+    }
+
+    fn keeps_going(&self) -> bool {
+        self.machines
+            .iter()
+            .any(|block| block.state == State::IteratingByte)
+    }
+}
+
+/// Steps through a recorded attack. Nothing is sent to any oracle, the
+/// machine is restored from the recorded states.
+impl Screen for Replay {
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>, controls: &Controls) {
+        ui::ui(
+            frame,
+            &self.machine,
+            controls.timeout,
+            controls.advance,
+            None,
+        )
+    }
+
+    fn advance(&mut self) -> anyhow::Result<()> {
+        self.forward();
+        Ok(())
+    }
+
+    fn is_done(&self) -> bool {
+        self.is_at_end()
+    }
+
+    /// Only `r` plays the recording on its own
+    fn keeps_going(&self) -> bool {
+        false
+    }
+
+    fn key(&mut self, code: KeyCode, controls: &mut Controls) -> anyhow::Result<()> {
+        match code {
+            KeyCode::Char('n') => controls.advance = true,
+            KeyCode::Char('b') => {
+                controls.run_without_pause = false;
+                self.back();
+            }
+            KeyCode::Home => self.seek(0),
+            KeyCode::End => self.seek(self.len() - 1),
+            _ => {}
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,320 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+
+use crate::oracle::{Oracle, PaddingOracle};
+
+const PADDING_OK: &str = "padding ok";
+const PADDING_ERROR: &str = "padding error";
+/// Hex-encoded iv and ciphertext fit easily, and a peer can't make us
+/// allocate more than that
+const MAX_BODY_LEN: usize = 16 * 1024;
+
+/// A tiny HTTP server, that exposes the oracle on a socket.
+///
+/// It understands only two requests:
+/// - `GET /ciphertext` returns the iv and the ciphertext, hex-encoded, one per line
+/// - `POST /oracle` with a hex-encoded ciphertext in the body returns
+///   `200 padding ok` or `500 padding error`
+pub struct Server {
+    listener: TcpListener,
+    oracle: Arc<Oracle>,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl Server {
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        oracle: Oracle,
+        iv: Vec<u8>,
+        ciphertext: Vec<u8>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            oracle: Arc::new(oracle),
+            iv,
+            ciphertext,
+        })
+    }
+
+    pub fn local_addr(&self) -> anyhow::Result<std::net::SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accepts connections forever, every connection is served in its own thread
+    pub fn run(&self) -> anyhow::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let oracle = self.oracle.clone();
+            let greeting = format!(
+                "{}\n{}\n",
+                hex::encode(&self.iv),
+                hex::encode(&self.ciphertext)
+            );
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                let before = oracle.counter();
+                // The client hanging up in the middle is not our problem
+                let _ = handle_connection(stream, &oracle, &greeting);
+                if let Some(peer) = peer {
+                    println!(
+                        "{peer} disconnected after {} queries ({} in total)",
+                        oracle.counter() - before,
+                        oracle.counter()
+                    );
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle_connection(stream: TcpStream, oracle: &Oracle, greeting: &str) -> anyhow::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    while let Some(request) = read_request(&mut reader)? {
+        let (status, body) = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/ciphertext") => ("200 OK", greeting.to_owned()),
+            ("POST", "/oracle") => match hex::decode(request.body.trim()) {
+                Ok(ciphertext) if oracle.query_decryption(&ciphertext) => {
+                    ("200 OK", PADDING_OK.to_owned())
+                }
+                Ok(_) => ("500 Internal Server Error", PADDING_ERROR.to_owned()),
+                Err(_) => (
+                    "400 Bad Request",
+                    "ciphertext should be hex-encoded".to_owned(),
+                ),
+            },
+            _ => ("404 Not Found", "not found".to_owned()),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        writer.write_all(response.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: String,
+}
+
+/// Reads one request. Returns `None` if the client closed the connection
+fn read_request(reader: &mut impl BufRead) -> anyhow::Result<Option<Request>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next().context("empty request line")?.to_owned();
+    let path = parts.next().context("no path in request line")?.to_owned();
+
+    let body = read_body(reader)?;
+    Ok(Some(Request { method, path, body }))
+}
+
+/// Skips headers and reads as many bytes of the body as `Content-Length` says
+fn read_body(reader: &mut impl BufRead) -> anyhow::Result<String> {
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            anyhow::bail!("connection closed in the middle of headers");
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_LEN {
+        anyhow::bail!("body of {content_length} bytes is too long, the limit is {MAX_BODY_LEN}");
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(String::from_utf8(body)?)
+}
+
+/// Client side of the `Server`. The attacker only sees the network endpoint
+/// and whether it answered `200` or not.
+pub struct RemoteOracle {
+    stream: Mutex<BufReader<TcpStream>>,
+    counter: AtomicUsize,
+    /// The first request that got no HTTP answer
+    error: Mutex<Option<String>>,
+}
+
+impl RemoteOracle {
+    pub fn connect(addr: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        // Queries are tiny, don't wait to batch them
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream: Mutex::new(BufReader::new(stream)),
+            counter: AtomicUsize::new(0),
+            error: Mutex::new(None),
+        })
+    }
+
+    /// Asks the server for the intercepted iv and ciphertext
+    pub fn fetch_ciphertext(&self) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+        let (_, body) = self.request("GET", "/ciphertext", "")?;
+        let mut lines = body.lines();
+        let iv = hex::decode(lines.next().context("no iv in response")?)?;
+        let ciphertext = hex::decode(lines.next().context("no ciphertext in response")?)?;
+        // The machine can't work with anything else, and the server may be anyone
+        if iv.is_empty() {
+            anyhow::bail!("the server sent an empty iv");
+        }
+        if ciphertext.is_empty() || ciphertext.len() % iv.len() != 0 {
+            anyhow::bail!(
+                "ciphertext length should be a multiple of {} bytes, the server sent {}",
+                iv.len(),
+                ciphertext.len()
+            );
+        }
+        Ok((iv, ciphertext))
+    }
+
+    fn request(&self, method: &str, path: &str, body: &str) -> anyhow::Result<(u16, String)> {
+        let mut stream = self.stream.lock().expect("poisoned");
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        stream.get_mut().write_all(request.as_bytes())?;
+
+        let mut status_line = String::new();
+        if stream.read_line(&mut status_line)? == 0 {
+            anyhow::bail!("server closed the connection");
+        }
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .context("no status in response")?
+            .parse()?;
+        let body = read_body(&mut *stream)?;
+        Ok((status, body))
+    }
+}
+
+impl PaddingOracle for RemoteOracle {
+    /// Any HTTP answer except `200` is treated as a padding error, that's
+    /// all the attacker can tell anyway. No answer at all is not a padding
+    /// error, it's kept for `error`
+    fn query(&self, ciphertext: &[u8]) -> bool {
+        self.counter.fetch_add(1, Ordering::Relaxed);
+        match self.request("POST", "/oracle", &hex::encode(ciphertext)) {
+            Ok((status, _)) => status == 200,
+            Err(err) => {
+                self.error
+                    .lock()
+                    .expect("poisoned")
+                    .get_or_insert_with(|| format!("{err:#}"));
+                false
+            }
+        }
+    }
+
+    fn counter(&self) -> usize {
+        self.counter.load(Ordering::Relaxed)
+    }
+
+    fn error(&self) -> Option<String> {
+        self.error.lock().expect("poisoned").clone()
+    }
+}
+
+#[test]
+fn test_remote_oracle() {
+    use crate::machine::{test_machine, DecryptingMachine};
+    use crate::oracle::{Cipher, PaddingScheme};
+
+    let plaintext = b"Hello Internet people";
+    let target = test_machine(plaintext, PaddingScheme::Pkcs7, Cipher::Aes128);
+
+    let server = Server::bind(
+        "127.0.0.1:0",
+        target.oracle,
+        target.initial_iv,
+        target.ciphertext,
+    )
+    .unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+
+    let remote = RemoteOracle::connect(addr).unwrap();
+    let (iv, ciphertext) = remote.fetch_ciphertext().unwrap();
+    let mut machine = DecryptingMachine::new(remote, iv, ciphertext);
+
+    let decrypted = machine.run_to_completion();
+    assert_eq!(&decrypted.plaintext[..plaintext.len()], plaintext);
+    assert_eq!(decrypted.queries, machine.oracle.counter());
+    assert_eq!(machine.oracle.error(), None);
+}
+
+#[test]
+fn test_server_gone() {
+    use crate::machine::{test_machine, DecryptingMachine, State};
+    use crate::oracle::{Cipher, PaddingScheme};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let target = test_machine(
+        b"Hello Internet people",
+        PaddingScheme::Pkcs7,
+        Cipher::Aes128,
+    );
+    // Hangs up right after the greeting
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let greeting = format!(
+            "{}\n{}\n",
+            hex::encode(&target.initial_iv),
+            hex::encode(&target.ciphertext)
+        );
+        read_request(&mut reader).unwrap();
+        write!(
+            &stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{greeting}",
+            greeting.len()
+        )
+        .unwrap();
+    });
+
+    let remote = RemoteOracle::connect(addr).unwrap();
+    let (iv, ciphertext) = remote.fetch_ciphertext().unwrap();
+    let mut machine = DecryptingMachine::new(remote, iv, ciphertext);
+    machine.run_to_completion();
+    assert_eq!(machine.state, State::Failed);
+    assert!(machine.oracle.error().is_some());
+    // Stopped at once, not after 256 guesses
+    assert_eq!(machine.oracle.counter(), 1);
+}
+
+#[test]
+fn test_body_limit() {
+    let request = format!("Content-Length: {}\r\n\r\n", MAX_BODY_LEN + 1);
+    assert!(read_body(&mut request.as_bytes()).is_err());
+
+    let request = "Content-Length: 2\r\n\r\nok";
+    assert_eq!(read_body(&mut request.as_bytes()).unwrap(), "ok");
+}
@@ -0,0 +1,362 @@
+use aes::cipher::{
+    block_padding::{AnsiX923, Iso10126, Iso7816, Padding, Pkcs7},
+    typenum::{U16, U8},
+    BlockCipher, BlockDecryptMut, BlockEncryptMut, KeyInit, KeyIvInit,
+};
+
+use hmac::{Hmac, Mac};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Block ciphers, that the oracle can use in CBC mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Cipher {
+    Aes128,
+    Aes192,
+    Aes256,
+    Des,
+    TripleDes,
+    Blowfish,
+}
+
+impl Cipher {
+    pub fn block_size(self) -> usize {
+        match self {
+            Self::Aes128 | Self::Aes192 | Self::Aes256 => 16,
+            Self::Des | Self::TripleDes | Self::Blowfish => 8,
+        }
+    }
+
+    pub fn key_size(self) -> usize {
+        match self {
+            Self::Aes128 => 16,
+            Self::Aes192 => 24,
+            Self::Aes256 => 32,
+            Self::Des => 8,
+            Self::TripleDes => 24,
+            Self::Blowfish => 16,
+        }
+    }
+
+    pub fn random_key(self) -> Vec<u8> {
+        self.random_key_from(&mut rand::thread_rng())
+    }
+
+    pub fn random_iv(self) -> Vec<u8> {
+        self.random_iv_from(&mut rand::thread_rng())
+    }
+
+    /// The same as `random_key`, but a seeded `rng` gives the same key every time
+    pub fn random_key_from(self, rng: &mut impl RngCore) -> Vec<u8> {
+        random_bytes(rng, self.key_size())
+    }
+
+    pub fn random_iv_from(self, rng: &mut impl RngCore) -> Vec<u8> {
+        random_bytes(rng, self.block_size())
+    }
+}
+
+fn random_bytes(rng: &mut impl RngCore, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Size of the HMAC-SHA256 tag, that follows the ciphertext in encrypt-then-MAC mode
+pub const TAG_SIZE: usize = 32;
+
+/// Padding schemes, that the oracle can check after decryption
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaddingScheme {
+    /// `04 04 04 04`: every byte is the length of the padding
+    #[default]
+    Pkcs7,
+    /// `00 00 00 04`: zeroes, and the last byte is the length
+    AnsiX923,
+    /// `?? ?? ?? 04`: random bytes, and the last byte is the length.
+    /// Only the last byte can be checked, so the oracle leaks very little
+    Iso10126,
+    /// `80 00 00 00`: a single set bit, followed by zeroes
+    Iso7816,
+}
+
+impl PaddingScheme {
+    /// Returns `len` bytes of padding. Random bytes of ISO 10126 are zeroes
+    /// here, nobody checks them anyway
+    pub fn pattern(self, len: usize) -> Vec<u8> {
+        let mut pattern = vec![0; len];
+        match self {
+            Self::Pkcs7 => pattern.fill(len as u8),
+            Self::AnsiX923 | Self::Iso10126 => pattern[len - 1] = len as u8,
+            Self::Iso7816 => pattern[0] = 0x80,
+        }
+        pattern
+    }
+
+    /// Pads the data to a multiple of `block_size`
+    pub fn pad(self, data: &[u8], block_size: usize) -> Vec<u8> {
+        let len = block_size - data.len() % block_size;
+        [data, &self.pattern(len)].concat()
+    }
+}
+
+/// A padding oracle is anything that can tell whether a ciphertext has a
+/// valid padding after decryption. The machine knows nothing else about it,
+/// so it may be a local struct, another process or a remote service.
+pub trait PaddingOracle {
+    /// Returns true if the padding of decrypted `ciphertext` is valid
+    fn query(&self, ciphertext: &[u8]) -> bool;
+
+    /// Returns the number of queries
+    fn counter(&self) -> usize;
+
+    /// Returns the first error that kept the oracle from answering at all.
+    /// After it, a `false` from `query` means nothing
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
+
+/// An Oracle is a `black box` which can only tell whether some ciphertext
+/// is a valid cbc or not.
+pub struct Oracle {
+    cipher: Cipher,
+    padding: PaddingScheme,
+    key: Vec<u8>,
+    iv: Vec<u8>,
+    /// Set in encrypt-then-MAC mode
+    mac_key: Option<Vec<u8>>,
+
+    counter: AtomicUsize,
+}
+
+impl Default for Oracle {
+    fn default() -> Self {
+        Self::new(Cipher::Aes128)
+    }
+}
+
+impl Oracle {
+    pub fn new(cipher: Cipher) -> Self {
+        Self::with_key(cipher, cipher.random_key(), cipher.random_iv())
+            .expect("random key and iv have the right size")
+    }
+
+    /// Creates an oracle with the key and iv derived from the seed, so the
+    /// whole attack goes the same way on every run
+    pub fn from_seed(cipher: Cipher, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let key = cipher.random_key_from(&mut rng);
+        let iv = cipher.random_iv_from(&mut rng);
+        Self::with_key(cipher, key, iv).expect("random key and iv have the right size")
+    }
+
+    /// Creates an oracle with the given key and iv, so it can be used
+    /// against a ciphertext that was encrypted elsewhere
+    pub fn with_key(cipher: Cipher, key: Vec<u8>, iv: Vec<u8>) -> anyhow::Result<Self> {
+        if key.len() != cipher.key_size() {
+            anyhow::bail!("{cipher:?} key should be {} bytes long", cipher.key_size());
+        }
+        if iv.len() != cipher.block_size() {
+            anyhow::bail!("{cipher:?} iv should be {} bytes long", cipher.block_size());
+        }
+
+        Ok(Self {
+            cipher,
+            padding: PaddingScheme::Pkcs7,
+            key,
+            iv,
+            mac_key: None,
+            counter: AtomicUsize::new(0),
+        })
+    }
+
+    /// Makes the oracle pad messages and check their padding with another scheme
+    pub fn with_padding(mut self, padding: PaddingScheme) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Turns on encrypt-then-MAC: an HMAC-SHA256 tag over the iv and the
+    /// ciphertext is appended to every message, and messages with a wrong tag
+    /// are rejected before unpadding. That's the fix for padding oracles.
+    pub fn with_mac(mut self, mac_key: Vec<u8>) -> Self {
+        self.mac_key = Some(mac_key);
+        self
+    }
+
+    pub fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
+    pub fn padding(&self) -> PaddingScheme {
+        self.padding
+    }
+
+    pub fn iv(&self) -> &[u8] {
+        &self.iv
+    }
+
+    /// Encrypts the data. In encrypt-then-MAC mode the tag is not included,
+    /// see `tag`
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        match self.padding {
+            PaddingScheme::Pkcs7 => self.encrypt_padded::<Pkcs7>(data),
+            PaddingScheme::AnsiX923 => self.encrypt_padded::<AnsiX923>(data),
+            PaddingScheme::Iso10126 => self.encrypt_padded::<Iso10126>(data),
+            PaddingScheme::Iso7816 => self.encrypt_padded::<Iso7816>(data),
+        }
+    }
+
+    /// In encrypt-then-MAC mode returns the tag, that the sender appends to
+    /// the ciphertext. It's not a cipherblock, so it's kept apart from the
+    /// ciphertext, that the machine attacks
+    pub fn tag(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+        let mac = self.mac(&self.iv, ciphertext)?;
+        Some(mac.finalize().into_bytes().to_vec())
+    }
+
+    /// Returns HMAC over the iv and the ciphertext, if the oracle has a MAC key
+    fn mac(&self, iv: &[u8], ciphertext: &[u8]) -> Option<HmacSha256> {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(self.mac_key.as_ref()?)
+            .expect("HMAC takes keys of any size");
+        mac.update(iv);
+        mac.update(ciphertext);
+        Some(mac)
+    }
+
+    fn encrypt_padded<P: Padding<U16> + Padding<U8>>(&self, data: &[u8]) -> Vec<u8> {
+        let (key, iv) = (&self.key, &self.iv);
+        match self.cipher {
+            Cipher::Aes128 => encrypt::<aes::Aes128, P>(key, iv, data),
+            Cipher::Aes192 => encrypt::<aes::Aes192, P>(key, iv, data),
+            Cipher::Aes256 => encrypt::<aes::Aes256, P>(key, iv, data),
+            Cipher::Des => encrypt::<des::Des, P>(key, iv, data),
+            Cipher::TripleDes => encrypt::<des::TdesEde3, P>(key, iv, data),
+            Cipher::Blowfish => encrypt::<blowfish::Blowfish, P>(key, iv, data),
+        }
+    }
+
+    /// Returns true if decryption is ok (which means padding is ok)
+    pub fn query_decryption(&self, ciphertext: &[u8]) -> bool {
+        self.counter.fetch_add(1, Ordering::Relaxed);
+        self.decrypt(&self.iv, ciphertext).is_some()
+    }
+
+    /// Decrypts and unpads the ciphertext with the oracle's key. The attack
+    /// never uses it, it's only here to check the results.
+    pub fn decrypt(&self, iv: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+        // The tag is checked first, so a tampered message never gets to unpadding
+        let ciphertext = if self.mac_key.is_some() {
+            let (ciphertext, tag) = ciphertext.split_at(ciphertext.len().checked_sub(TAG_SIZE)?);
+            self.mac(iv, ciphertext)?.verify_slice(tag).ok()?;
+            ciphertext
+        } else {
+            ciphertext
+        };
+
+        match self.padding {
+            PaddingScheme::Pkcs7 => self.decrypt_padded::<Pkcs7>(iv, ciphertext),
+            PaddingScheme::AnsiX923 => self.decrypt_padded::<AnsiX923>(iv, ciphertext),
+            PaddingScheme::Iso10126 => self.decrypt_padded::<Iso10126>(iv, ciphertext),
+            PaddingScheme::Iso7816 => self.decrypt_padded::<Iso7816>(iv, ciphertext),
+        }
+    }
+
+    fn decrypt_padded<P>(&self, iv: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>>
+    where
+        P: Padding<U16> + Padding<U8>,
+    {
+        let key = &self.key;
+        match self.cipher {
+            Cipher::Aes128 => decrypt::<aes::Aes128, P>(key, iv, ciphertext),
+            Cipher::Aes192 => decrypt::<aes::Aes192, P>(key, iv, ciphertext),
+            Cipher::Aes256 => decrypt::<aes::Aes256, P>(key, iv, ciphertext),
+            Cipher::Des => decrypt::<des::Des, P>(key, iv, ciphertext),
+            Cipher::TripleDes => decrypt::<des::TdesEde3, P>(key, iv, ciphertext),
+            Cipher::Blowfish => decrypt::<blowfish::Blowfish, P>(key, iv, ciphertext),
+        }
+    }
+
+    /// Returns the number of queries
+    pub fn counter(&self) -> usize {
+        self.counter.load(Ordering::Relaxed)
+    }
+}
+
+fn encrypt<C, P>(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8>
+where
+    C: BlockCipher + BlockEncryptMut + KeyInit,
+    P: Padding<C::BlockSize>,
+{
+    cbc::Encryptor::<C>::new_from_slices(key, iv)
+        .expect("key and iv sizes match the cipher")
+        .encrypt_padded_vec_mut::<P>(data)
+}
+
+fn decrypt<C, P>(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>>
+where
+    C: BlockCipher + BlockDecryptMut + KeyInit,
+    P: Padding<C::BlockSize>,
+{
+    cbc::Decryptor::<C>::new_from_slices(key, iv)
+        .ok()?
+        .decrypt_padded_vec_mut::<P>(ciphertext)
+        .ok()
+}
+
+impl PaddingOracle for Oracle {
+    fn query(&self, ciphertext: &[u8]) -> bool {
+        self.query_decryption(ciphertext)
+    }
+
+    fn counter(&self) -> usize {
+        Oracle::counter(self)
+    }
+}
+
+impl<O: PaddingOracle + ?Sized> PaddingOracle for Box<O> {
+    fn query(&self, ciphertext: &[u8]) -> bool {
+        (**self).query(ciphertext)
+    }
+
+    fn counter(&self) -> usize {
+        (**self).counter()
+    }
+
+    fn error(&self) -> Option<String> {
+        (**self).error()
+    }
+}
+
+impl<O: PaddingOracle + ?Sized> PaddingOracle for Arc<O> {
+    fn query(&self, ciphertext: &[u8]) -> bool {
+        (**self).query(ciphertext)
+    }
+
+    fn counter(&self) -> usize {
+        (**self).counter()
+    }
+
+    fn error(&self) -> Option<String> {
+        (**self).error()
+    }
+}
+
+#[test]
+fn test_seeded_oracle() {
+    let plaintext = b"Hello Internet";
+    let first = Oracle::from_seed(Cipher::Aes128, 42);
+    let second = Oracle::from_seed(Cipher::Aes128, 42);
+    let other = Oracle::from_seed(Cipher::Aes128, 43);
+
+    assert_eq!(first.iv(), second.iv());
+    assert_eq!(first.encrypt(plaintext), second.encrypt(plaintext));
+    assert_ne!(first.encrypt(plaintext), other.encrypt(plaintext));
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::machine::{Decrypted, DecryptingMachine, Strategy};
+use crate::oracle::{Oracle, PaddingOracle, PaddingScheme};
+
+/// Attacks every block of the ciphertext at the same time.
+///
+/// In CBC a block depends only on itself and the previous ciphertext block,
+/// which the attacker already has. So every block gets its own
+/// `DecryptingMachine`, that sees a one-block message with the previous
+/// block as the iv, and all of them share the same oracle.
+pub struct ParallelMachine<O = Oracle> {
+    /// Machines for every block, in order
+    pub machines: Vec<DecryptingMachine<Arc<O>>>,
+    pub oracle: Arc<O>,
+    pub block_size: usize,
+}
+
+impl<O: PaddingOracle + Send + Sync> ParallelMachine<O> {
+    pub fn new(oracle: O, initial_iv: Vec<u8>, ciphertext: Vec<u8>) -> Self {
+        let oracle = Arc::new(oracle);
+        let block_size = initial_iv.len();
+
+        let ivs = std::iter::once(&initial_iv[..]).chain(ciphertext.chunks(block_size));
+        let machines = ivs
+            .zip(ciphertext.chunks(block_size))
+            .map(|(iv, block)| DecryptingMachine::new(oracle.clone(), iv.to_vec(), block.to_vec()))
+            .collect();
+
+        Self {
+            machines,
+            oracle,
+            block_size,
+        }
+    }
+
+    /// Every machine thinks its block is the last one, so only the real
+    /// last block looks for the padding
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        let last = self.machines.len().saturating_sub(1);
+        for (num, machine) in self.machines.iter_mut().enumerate() {
+            machine.strategy = match strategy {
+                Strategy::Padding if num != last => Strategy::Frequency,
+                _ => strategy,
+            };
+        }
+        self
+    }
+
+    pub fn with_padding_scheme(mut self, scheme: PaddingScheme) -> Self {
+        for machine in &mut self.machines {
+            machine.scheme = scheme;
+        }
+        self
+    }
+
+    /// Advances all machines by one step, each in its own thread
+    pub fn advance(&mut self) {
+        self.machines
+            .par_iter_mut()
+            .filter(|machine| !machine.is_done())
+            .for_each(|machine| machine.advance());
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.machines.iter().all(|machine| machine.is_done())
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.machines.iter().any(|machine| machine.is_failed())
+    }
+
+    /// Runs all machines until the end on the thread pool
+    pub fn run_to_completion(&mut self) -> Decrypted {
+        self.machines.par_iter_mut().for_each(|machine| {
+            machine.run_to_completion();
+        });
+
+        Decrypted {
+            plaintext: self
+                .machines
+                .iter()
+                .flat_map(|machine| machine.decrypted.iter().copied())
+                .collect(),
+            queries: self.machines.iter().map(|machine| machine.queries()).sum(),
+            naive_queries: self
+                .machines
+                .iter()
+                .flat_map(|machine| &machine.costs)
+                .map(|cost| cost.naive_queries)
+                .sum(),
+        }
+    }
+}
+
+#[test]
+fn test_parallel() {
+    use crate::machine::test_machine;
+    use crate::oracle::Cipher;
+
+    let plaintext = b"Every block is attacked on its own thread";
+    let target = test_machine(plaintext, PaddingScheme::Pkcs7, Cipher::Aes128);
+
+    let mut machine = ParallelMachine::new(target.oracle, target.initial_iv, target.ciphertext)
+        .with_strategy(Strategy::Padding);
+    assert_eq!(machine.machines.len(), 3);
+
+    let decrypted = machine.run_to_completion();
+    assert_eq!(&decrypted.plaintext[..plaintext.len()], plaintext);
+    assert_eq!(decrypted.plaintext[plaintext.len()..], [7; 7]);
+    let per_block: usize = machine.machines.iter().map(|m| m.queries()).sum();
+    assert_eq!(decrypted.queries, per_block);
+}
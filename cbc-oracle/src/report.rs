@@ -0,0 +1,116 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::machine::{DecryptingMachine, State, Strategy};
+use crate::oracle::{PaddingOracle, PaddingScheme};
+
+/// Summary of an attack, that can be attached to a write-up
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Report {
+    /// False if the machine was stopped or failed before the end
+    pub finished: bool,
+    pub strategy: Strategy,
+    pub padding: PaddingScheme,
+    #[serde(with = "hex::serde")]
+    pub iv: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub ciphertext: Vec<u8>,
+    /// Recovered blocks, padding included
+    #[serde(with = "hex::serde")]
+    pub plaintext: Vec<u8>,
+    /// The same plaintext as text, with invalid UTF-8 replaced
+    pub text: String,
+    /// Number of queries the machine needed, comparable with `naive_queries`
+    pub queries: usize,
+    pub naive_queries: usize,
+    /// Queries spent on every plaintext byte, in the order of the plaintext
+    pub byte_queries: Vec<usize>,
+}
+
+impl Report {
+    pub fn new<O: PaddingOracle>(machine: &DecryptingMachine<O>) -> Self {
+        let plaintext = machine.decrypted.clone();
+        // A replayed machine has no costs
+        let costs = &machine.costs[..plaintext.len().min(machine.costs.len())];
+
+        // Bytes of every block are found from the last one to the first
+        let byte_queries = costs
+            .chunks(machine.block_size)
+            .flat_map(|block| block.iter().rev().map(|cost| cost.queries))
+            .collect();
+
+        Self {
+            finished: machine.state == State::Finished,
+            strategy: machine.strategy,
+            padding: machine.scheme,
+            iv: machine.initial_iv.clone(),
+            ciphertext: machine.ciphertext.clone(),
+            text: String::from_utf8_lossy(&plaintext).into_owned(),
+            plaintext,
+            queries: machine.queries(),
+            naive_queries: costs.iter().map(|cost| cost.naive_queries).sum(),
+            byte_queries,
+        }
+    }
+
+    /// Writes the report as Markdown, if the file ends with `.md`, or as JSON
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("md") => self.to_markdown(),
+            _ => serde_json::to_string_pretty(self)? + "\n",
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let status = if self.finished {
+            "finished"
+        } else {
+            "not finished"
+        };
+
+        // Writing to a String never fails
+        let _ = writeln!(md, "# Padding oracle attack report\n");
+        let _ = writeln!(md, "- Status: {status}");
+        let _ = writeln!(md, "- Strategy: {:?}", self.strategy);
+        let _ = writeln!(md, "- Padding: {:?}", self.padding);
+        let _ = writeln!(md, "- Queries: {}", self.queries);
+        let _ = writeln!(md, "- Naive strategy would make: {}", self.naive_queries);
+        let _ = writeln!(md, "- IV: `{}`", hex::encode(&self.iv));
+        let _ = writeln!(md, "- Ciphertext: `{}`", hex::encode(&self.ciphertext));
+        let _ = writeln!(md, "- Plaintext: `{}`\n", hex::encode(&self.plaintext));
+        let _ = writeln!(md, "```\n{:?}\n```\n", self.text);
+
+        let _ = writeln!(md, "## Queries per byte\n");
+        let _ = writeln!(md, "| byte | value | queries |");
+        let _ = writeln!(md, "|-----:|:-----:|--------:|");
+        for (num, (byte, queries)) in self.plaintext.iter().zip(&self.byte_queries).enumerate() {
+            let _ = writeln!(md, "| {num} | `{byte:02x}` | {queries} |");
+        }
+
+        md
+    }
+}
+
+#[test]
+fn test_report() {
+    use crate::machine::test_machine;
+    use crate::oracle::Cipher;
+
+    let plaintext = b"Hello Internet people";
+    let mut machine = test_machine(plaintext, PaddingScheme::Pkcs7, Cipher::Aes128);
+    let decrypted = machine.run_to_completion();
+
+    let report = Report::new(&machine);
+    assert!(report.finished);
+    assert_eq!(report.plaintext, decrypted.plaintext);
+    assert_eq!(report.queries, decrypted.queries);
+    assert_eq!(report.byte_queries.len(), 32);
+    assert_eq!(report.byte_queries.iter().sum::<usize>(), decrypted.queries);
+    assert!(report.to_markdown().contains("| 31 | `0b` |"));
+}
@@ -0,0 +1,154 @@
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::oracle::{Oracle, PaddingOracle};
+
+/// Time the server spends on messages with a valid padding. It stands in for
+/// a MAC, that is verified only after the message was successfully unpadded.
+const MAC_DELAY: Duration = Duration::from_millis(1);
+
+/// Number of random queries used to learn how fast a padding error is
+const CALIBRATION_QUERIES: usize = 32;
+
+/// The same oracle, but it answers with the same error no matter what went wrong.
+/// Still, it leaks: a valid padding is followed by a MAC check, which takes time.
+pub struct TimingOracle {
+    oracle: Oracle,
+    /// How long the MAC check takes
+    delay: Duration,
+}
+
+impl TimingOracle {
+    pub fn new(oracle: Oracle) -> Self {
+        Self::with_delay(oracle, MAC_DELAY)
+    }
+
+    /// A slower MAC is easier to tell from noise
+    pub fn with_delay(oracle: Oracle, delay: Duration) -> Self {
+        Self { oracle, delay }
+    }
+
+    pub fn iv(&self) -> &[u8] {
+        self.oracle.iv()
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        self.oracle.encrypt(data)
+    }
+
+    /// Returns nothing, every forged message is rejected anyway
+    pub fn query_decryption(&self, ciphertext: &[u8]) {
+        if self.oracle.query_decryption(ciphertext) {
+            // A forged message will never pass the MAC check, but checking
+            // it takes a while
+            std::thread::sleep(self.delay);
+        }
+    }
+
+    /// Returns the number of queries
+    pub fn counter(&self) -> usize {
+        self.oracle.counter()
+    }
+}
+
+/// Turns response time of `TimingOracle` into a padding oracle.
+///
+/// A single measurement is noisy, so every query is repeated `samples` times
+/// and the fastest one is compared with a threshold. Noise, like other busy
+/// threads, can only make a query slower, so the fastest sample is the closest
+/// to the real time. The threshold is halfway between a padding error, that is
+/// calibrated the same way on random ciphertexts, which almost never have a
+/// valid padding, and a padding error with the MAC check after it.
+pub struct TimingProbe {
+    oracle: TimingOracle,
+    samples: usize,
+    threshold: Duration,
+}
+
+impl TimingProbe {
+    pub fn new(oracle: TimingOracle, samples: usize) -> Self {
+        let mut rng = rand::thread_rng();
+        let baseline = (0..CALIBRATION_QUERIES)
+            .map(|_| {
+                let ciphertext: [u8; 32] = rng.gen();
+                measure(&oracle, &ciphertext)
+            })
+            .min()
+            .expect("there are calibration queries");
+
+        Self {
+            threshold: threshold(baseline, oracle.delay),
+            oracle,
+            samples: samples.max(1),
+        }
+    }
+}
+
+impl PaddingOracle for TimingProbe {
+    fn query(&self, ciphertext: &[u8]) -> bool {
+        let fastest = (0..self.samples)
+            .map(|_| measure(&self.oracle, ciphertext))
+            .min()
+            .expect("there is at least one sample");
+        fastest > self.threshold
+    }
+
+    /// Counts every sample, since each of them is a request to the server
+    fn counter(&self) -> usize {
+        self.oracle.counter()
+    }
+}
+
+/// The MAC delay is known, it's the same for every valid padding. A
+/// multiple of the baseline would depend on how fast the calibration was,
+/// while halfway leaves as much room for noise on both sides
+fn threshold(baseline: Duration, delay: Duration) -> Duration {
+    baseline + delay / 2
+}
+
+fn measure(oracle: &TimingOracle, ciphertext: &[u8]) -> Duration {
+    let start = Instant::now();
+    oracle.query_decryption(ciphertext);
+    start.elapsed()
+}
+
+/// With a MAC this slow, no noise can hide it
+#[test]
+fn test_timing_query() {
+    use crate::machine::test_machine;
+    use crate::oracle::{Cipher, PaddingScheme};
+
+    let target = test_machine(
+        b"Hello Internet people",
+        PaddingScheme::Pkcs7,
+        Cipher::Aes128,
+    );
+    let oracle = TimingOracle::with_delay(target.oracle, Duration::from_millis(20));
+    let ciphertext = target.ciphertext;
+    // The last byte of the padding is 0a instead of 0b now
+    let mut invalid = ciphertext.clone();
+    invalid[15] ^= 0x01;
+
+    let probe = TimingProbe::new(oracle, 3);
+    assert!(probe.query(&ciphertext));
+    assert!(!probe.query(&invalid));
+    // Every sample is a request
+    assert_eq!(probe.counter(), CALIBRATION_QUERIES + 2 * 3);
+}
+
+#[test]
+#[ignore = "measures real time, so depends on the load of the machine"]
+fn test_timing_probe() {
+    use crate::machine::{test_machine, DecryptingMachine};
+    use crate::oracle::{Cipher, PaddingScheme};
+
+    let plaintext = b"Hello Internet";
+    let target = test_machine(plaintext, PaddingScheme::Pkcs7, Cipher::Aes128);
+
+    let probe = TimingProbe::new(TimingOracle::new(target.oracle), 5);
+    let mut machine = DecryptingMachine::new(probe, target.initial_iv, target.ciphertext);
+
+    let decrypted = machine.run_to_completion();
+    assert_eq!(&decrypted.plaintext[..plaintext.len()], plaintext);
+}
@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::machine::{DecryptingMachine, Snapshot, Strategy};
+use crate::oracle::{PaddingOracle, PaddingScheme};
+
+/// One line of a trace. A trace starts with `Start`, then queries and
+/// states follow in the order they happened.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The message that is being attacked
+    Start {
+        #[serde(with = "hex::serde")]
+        iv: Vec<u8>,
+        #[serde(with = "hex::serde")]
+        ciphertext: Vec<u8>,
+        strategy: Strategy,
+        #[serde(default)]
+        padding: PaddingScheme,
+    },
+    /// A crafted ciphertext sent to the oracle, and its answer
+    Query {
+        #[serde(with = "hex::serde")]
+        ciphertext: Vec<u8>,
+        ok: bool,
+    },
+    /// The machine after a transition
+    State(Snapshot),
+}
+
+/// Writes events as JSON Lines. Clones write to the same file, so the
+/// machine and its oracle can share one trace.
+#[derive(Clone)]
+pub struct TraceWriter {
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl TraceWriter {
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::create(path.as_ref())
+            .with_context(|| format!("can't create {}", path.as_ref().display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+
+    pub fn write(&self, event: &Event) -> anyhow::Result<()> {
+        let mut file = self.file.lock().expect("poisoned");
+        serde_json::to_writer(&mut *file, event)?;
+        file.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Writes the starting event and the initial state of the machine
+    pub fn start<O: PaddingOracle>(&self, machine: &DecryptingMachine<O>) -> anyhow::Result<()> {
+        self.write(&Event::Start {
+            iv: machine.initial_iv.clone(),
+            ciphertext: machine.ciphertext.clone(),
+            strategy: machine.strategy,
+            padding: machine.scheme,
+        })?;
+        self.state(machine)
+    }
+
+    pub fn state<O: PaddingOracle>(&self, machine: &DecryptingMachine<O>) -> anyhow::Result<()> {
+        self.write(&Event::State(machine.snapshot()))
+    }
+
+    pub fn flush(&self) -> anyhow::Result<()> {
+        Ok(self.file.lock().expect("poisoned").flush()?)
+    }
+}
+
+/// Passes queries to the oracle and writes each of them to the trace
+pub struct Recording<O> {
+    oracle: O,
+    trace: TraceWriter,
+}
+
+impl<O> Recording<O> {
+    pub fn new(oracle: O, trace: TraceWriter) -> Self {
+        Self { oracle, trace }
+    }
+}
+
+impl<O: PaddingOracle> PaddingOracle for Recording<O> {
+    fn query(&self, ciphertext: &[u8]) -> bool {
+        let ok = self.oracle.query(ciphertext);
+        // The oracle can't fail, the machine writes its states to the same
+        // file and will notice a broken trace
+        let _ = self.trace.write(&Event::Query {
+            ciphertext: ciphertext.to_vec(),
+            ok,
+        });
+        ok
+    }
+
+    fn counter(&self) -> usize {
+        self.oracle.counter()
+    }
+
+    fn error(&self) -> Option<String> {
+        self.oracle.error()
+    }
+}
+
+/// A recorded attack, that can be stepped through in both directions
+pub struct Replay {
+    pub machine: DecryptingMachine<ReplayOracle>,
+    states: Vec<Snapshot>,
+    position: usize,
+}
+
+impl Replay {
+    /// Reads the trace. Queries are not needed to replay the machine, every
+    /// state already has the last crafted block and the answer to it.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path.as_ref())
+            .with_context(|| format!("can't open {}", path.as_ref().display()))?;
+
+        let mut start = None;
+        let mut states = vec![];
+        for (num, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = serde_json::from_str(&line)
+                .with_context(|| format!("invalid event on line {}", num + 1))?;
+            match event {
+                Event::Start { .. } if start.is_some() => {
+                    anyhow::bail!("second start event on line {}", num + 1)
+                }
+                Event::Start { .. } => start = Some(event),
+                Event::State(snapshot) => states.push(snapshot),
+                Event::Query { .. } => {}
+            }
+        }
+
+        let (iv, ciphertext, strategy, padding) = match start {
+            Some(Event::Start {
+                iv,
+                ciphertext,
+                strategy,
+                padding,
+            }) => (iv, ciphertext, strategy, padding),
+            _ => anyhow::bail!("trace has no start event"),
+        };
+        if states.is_empty() {
+            anyhow::bail!("trace has no states");
+        }
+
+        let machine = DecryptingMachine::new(ReplayOracle::default(), iv, ciphertext)
+            .with_strategy(strategy)
+            .with_padding_scheme(padding);
+        let mut replay = Self {
+            machine,
+            states,
+            position: 0,
+        };
+        replay.seek(0);
+        Ok(replay)
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.position + 1 == self.states.len()
+    }
+
+    pub fn forward(&mut self) {
+        self.seek(self.position + 1);
+    }
+
+    pub fn back(&mut self) {
+        self.seek(self.position.saturating_sub(1));
+    }
+
+    /// Restores the machine to the recorded state with the given index
+    pub fn seek(&mut self, position: usize) {
+        self.position = position.min(self.states.len() - 1);
+        let snapshot = &self.states[self.position];
+        self.machine.restore(snapshot);
+        self.machine.oracle.set_counter(snapshot.queries);
+    }
+}
+
+/// Stands in for the oracle during a replay. It never answers, it only shows
+/// how many queries were made at the moment.
+#[derive(Default)]
+pub struct ReplayOracle {
+    counter: AtomicUsize,
+}
+
+impl ReplayOracle {
+    fn set_counter(&self, counter: usize) {
+        self.counter.store(counter, Ordering::Relaxed);
+    }
+}
+
+impl PaddingOracle for ReplayOracle {
+    fn query(&self, _ciphertext: &[u8]) -> bool {
+        unreachable!("replayed machine is only restored, never advanced")
+    }
+
+    fn counter(&self) -> usize {
+        self.counter.load(Ordering::Relaxed)
+    }
+}
+
+#[test]
+fn test_record_and_replay() {
+    use crate::machine::test_machine;
+    use crate::oracle::{Cipher, PaddingScheme};
+
+    let path = std::env::temp_dir().join(format!("cbc-oracle-{}.jsonl", std::process::id()));
+    let trace = TraceWriter::create(&path).unwrap();
+
+    let target = test_machine(b"Hello Internet", PaddingScheme::Pkcs7, Cipher::Aes128);
+    let oracle = Recording::new(target.oracle, trace.clone());
+    let mut machine = DecryptingMachine::new(oracle, target.initial_iv, target.ciphertext);
+
+    trace.start(&machine).unwrap();
+    let mut states = vec![machine.snapshot()];
+    while !machine.is_done() {
+        machine.advance();
+        trace.state(&machine).unwrap();
+        states.push(machine.snapshot());
+    }
+    trace.flush().unwrap();
+
+    let mut replay = Replay::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(replay.len(), states.len());
+
+    while !replay.is_at_end() {
+        replay.forward();
+    }
+    assert_eq!(replay.machine.snapshot(), states[states.len() - 1]);
+    assert_eq!(replay.machine.decrypted, machine.decrypted);
+
+    replay.back();
+    assert_eq!(replay.machine.snapshot(), states[states.len() - 2]);
+}
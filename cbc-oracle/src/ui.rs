@@ -1,4 +1,8 @@
-use crate::machine::{DecryptingMachine, BLOCK_SIZE};
+use crate::explain::explain;
+use crate::forge::{ForgeState, ForgingMachine};
+use crate::machine::DecryptingMachine;
+use crate::oracle::PaddingOracle;
+use crate::parallel::ParallelMachine;
 
 use std::time::Duration;
 use tui::layout::Rect;
@@ -21,33 +25,202 @@ const STYLE_PADDING: fn() -> Style = || Style::default().fg(Color::White);
 const STYLE_CIPHERTEXT: fn() -> Style = || Style::default().fg(Color::Yellow);
 const STYLE_COUNTER: fn() -> Style = || Style::default().fg(Color::Cyan);
 
-pub fn ui<B: Backend>(
+/// Width of the longest name in the variables table
+const VARIABLE_NAME_WIDTH: u16 = 20;
+
+/// Variables, oracle, computations, decrypted text and the footer, when
+/// they are stacked
+const MIN_HEIGHT: u16 = 7 + 3 + 3 + 3 + 1;
+
+/// Below this size panels are stacked on top of each other, and the less
+/// important ones are hidden
+const COMPACT_WIDTH: u16 = 120;
+const COMPACT_HEIGHT: u16 = 30;
+
+/// Result of the last action, shown in the footer until the next one
+pub enum Status {
+    Info(String),
+    Error(String),
+}
+
+pub fn ui<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    timeout: Duration,
+    advance: bool,
+    status: Option<&Status>,
+) {
+    let size = f.size();
+    let min_width = min_width(machine.block_size);
+    if size.width < min_width || size.height < MIN_HEIGHT {
+        render_screen_too_small(f, size, min_width, MIN_HEIGHT);
+        return;
+    }
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(size);
+
+    let (top, footer) = (layout[0], layout[1]);
+
+    if is_compact(size) {
+        render_compact_panels(f, machine, top);
+    } else {
+        render_panels(f, machine, top);
+    }
+    render_footer(f, machine, timeout, advance, status, footer);
+
+    match machine.state {
+        State::Start => render_press_any_key(f, f.size()),
+        State::Failed => render_failed(f, machine.oracle.error(), f.size()),
+        _ => {}
+    }
+}
+
+/// The same screen, but for the machine that forges a ciphertext. Most of the
+/// panels show its inner decrypting machine.
+pub fn forge_ui<B: Backend, O: PaddingOracle>(
     f: &mut Frame<B>,
-    machine: &DecryptingMachine,
+    machine: &ForgingMachine<O>,
     timeout: Duration,
     advance: bool,
 ) {
     let size = f.size();
+    let min_width = min_width(machine.block_size());
+    if size.width < min_width || size.height < MIN_HEIGHT {
+        render_screen_too_small(f, size, min_width, MIN_HEIGHT);
+        return;
+    }
+
     let layout = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(size.height - 1), Constraint::Length(1)])
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
         .split(size);
 
     let (top, footer) = (layout[0], layout[1]);
 
+    // The forged blocks are also in the forgery panel, so they are the first
+    // to go on a small screen
+    let compact = is_compact(size);
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(if compact {
+            [
+                Constraint::Length(7),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ]
+        } else {
+            [
+                Constraint::Percentage(50),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Percentage(20),
+            ]
+        })
+        .split(top);
+    let top_panel = vertical[0];
+    let oracle_panel = vertical[1];
+    let computations_panel = vertical[2];
+    let forgery_panel = vertical[3];
+
+    if compact {
+        render_variable_table(f, &machine.decrypting, top_panel);
+    } else {
+        let horizontal = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+            .split(top_panel);
+
+        render_forged_blocks_panel(f, machine, horizontal[0]);
+        render_variable_table(f, &machine.decrypting, horizontal[1]);
+    }
+    render_oracle_panel(f, &machine.decrypting, oracle_panel);
+    if machine.state == ForgeState::ForgingPreviousBlock {
+        render_forging_computations(f, machine, computations_panel);
+    } else {
+        render_computations(f, &machine.decrypting, computations_panel);
+    }
+    render_forgery_panel(f, machine, forgery_panel);
+    render_footer(f, &machine.decrypting, timeout, advance, None, footer);
+
+    match machine.state {
+        ForgeState::Start => render_press_any_key(f, f.size()),
+        ForgeState::Failed => render_failed(f, machine.decrypting.oracle.error(), f.size()),
+        _ => {}
+    }
+}
+
+/// Progress of every block, when all of them are attacked at once
+pub fn parallel_ui<B: Backend, O: PaddingOracle + Send + Sync>(
+    f: &mut Frame<B>,
+    machine: &ParallelMachine<O>,
+    timeout: Duration,
+    advance: bool,
+) {
+    let size = f.size();
+    // Everything but the plaintext column, which is cut when it doesn't fit
+    let min_width = 2 + 6 + machine.block_size as u16 + 7 + 28 + 8 + 4;
+    // At least one block, with the header and borders
+    let min_height = 4 + 5 + 1;
+    if size.width < min_width || size.height < min_height {
+        render_screen_too_small(f, size, min_width, min_height);
+        return;
+    }
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(5),
+            Constraint::Length(1),
+        ])
+        .split(size);
+
+    let (blocks_panel, decrypted_panel, footer) = (layout[0], layout[1], layout[2]);
+
+    render_parallel_blocks(f, machine, blocks_panel);
+    render_parallel_decrypted(f, machine, decrypted_panel);
+
+    let processing = if advance { "[running]" } else { "[paused]" };
+    let running = machine.machines.iter().filter(|m| !m.is_done()).count();
+    let queries = machine.oracle.counter();
+    let paragraph = Paragraph::new(format!(
+        "{processing} {timeout:?} {running} blocks running, {queries} queries "
+    ))
+    .style(Style::default().bg(Color::DarkGray))
+    .alignment(Alignment::Right);
+    f.render_widget(paragraph, footer);
+
+    if machine.machines.iter().all(|m| m.state == State::Start) {
+        render_press_any_key(f, f.size())
+    }
+}
+
+/// The cipherblocks and the variables side by side, with the explanation
+/// and the decrypted text below
+fn render_panels<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    top: Rect,
+) {
     let vertical = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Percentage(50),
+            Constraint::Percentage(45),
             Constraint::Length(3),
             Constraint::Length(3),
-            Constraint::Percentage(20),
+            Constraint::Length(7),
+            Constraint::Min(3),
         ])
         .split(top);
     let top_panel = vertical[0];
     let oracle_panel = vertical[1];
     let computations_panel = vertical[2];
-    let decrypted_panel = vertical[3];
+    let explanation_panel = vertical[3];
+    let decrypted_panel = vertical[4];
 
     let horizontal = Layout::default()
         .direction(Direction::Horizontal)
@@ -63,15 +236,254 @@ pub fn ui<B: Backend>(
     render_oracle_panel(f, machine, oracle_panel);
 
     render_computations(f, machine, computations_panel);
-    render_footer(f, machine, timeout, advance, footer);
+    render_explanation(f, machine, explanation_panel);
+}
 
-    if let State::Start = machine.state {
-        render_press_any_key(f, f.size())
+/// Panels stacked on top of each other for narrow or low terminals. The
+/// cipherblocks are hidden, the explanation gets whatever room is left.
+fn render_compact_panels<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    top: Rect,
+) {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(7),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .split(top);
+
+    render_variable_table(f, machine, vertical[0]);
+    render_oracle_panel(f, machine, vertical[1]);
+    render_computations(f, machine, vertical[2]);
+    // Borders and at least one line
+    if vertical[3].height >= 3 {
+        render_explanation(f, machine, vertical[3]);
+    }
+    render_decrypted_panel(f, machine, vertical[4]);
+}
+
+fn render_parallel_blocks<B: Backend, O: PaddingOracle + Send + Sync>(
+    f: &mut Frame<B>,
+    machine: &ParallelMachine<O>,
+    panel: Rect,
+) {
+    let block_size = machine.block_size;
+    let rows = machine.machines.iter().enumerate().map(|(num, block)| {
+        let plain = [&block.decrypted[..], &block.known].concat();
+        let progress = format!(
+            "{}{} {:>2}/{block_size}",
+            "#".repeat(plain.len()),
+            ".".repeat(block_size - plain.len()),
+            plain.len()
+        );
+        let style = match block.state {
+            State::Finished => STYLE_KNOWN(),
+            State::Failed => Style::default().fg(Color::Red),
+            _ => STYLE_COUNTER(),
+        };
+
+        Row::new([
+            Cell::from(format!("{}", num + 1)),
+            Cell::from(progress).style(style),
+            Cell::from(format!("{:?}", block.state)),
+            Cell::from(format!("{}", block.queries())),
+            Cell::from(format_block(&plain, block_size, STYLE_KNOWN())),
+        ])
+    });
+
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Length(block_size as u16 + 7),
+        Constraint::Length(28),
+        Constraint::Length(8),
+        Constraint::Min(0),
+    ];
+    let table = Table::new(rows)
+        .header(
+            Row::new(["block", "progress", "state", "queries", "plaintext"])
+                .style(Style::default().fg(Color::White)),
+        )
+        .widths(&widths)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Blocks")
+                .title_alignment(Alignment::Center),
+        );
+
+    f.render_widget(table, panel);
+}
+
+fn render_parallel_decrypted<B: Backend, O: PaddingOracle + Send + Sync>(
+    f: &mut Frame<B>,
+    machine: &ParallelMachine<O>,
+    panel: Rect,
+) {
+    // Bytes, that are not known yet, are shown as dots
+    let decrypted = machine
+        .machines
+        .iter()
+        .flat_map(|block| {
+            let plain = [&block.decrypted[..], &block.known].concat();
+            let mut text = ".".repeat(machine.block_size - plain.len());
+            text.push_str(&String::from_utf8_lossy(&plain));
+            text.chars().collect::<Vec<_>>()
+        })
+        .collect::<String>();
+
+    let paragraph = Paragraph::new(decrypted)
+        .style(Style::default().fg(Color::Green))
+        .wrap(tui::widgets::Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Decrypted")
+                .title_alignment(Alignment::Center),
+        );
+
+    f.render_widget(paragraph, panel);
+}
+
+fn render_forged_blocks_panel<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &ForgingMachine<O>,
+    panel: Rect,
+) {
+    let block_size = machine.block_size();
+    let plainblocks = machine.plaintext.chunks(block_size).collect::<Vec<_>>();
+    // Forged blocks are the last ones, including the current block
+    let forged = machine.forged.chunks(block_size).collect::<Vec<_>>();
+    let first_forged = plainblocks.len() + 1 - forged.len();
+
+    // Block 0 is the iv, plaintext blocks are numerated from 1
+    let items = (0..=plainblocks.len())
+        .map(|i: usize| {
+            let cipherblock = match i.checked_sub(first_forged) {
+                Some(j) if j == 0 => Span::styled(hex::encode(forged[j]), STYLE_CIPHERTEXT()),
+                Some(j) => Span::raw(hex::encode(forged[j])),
+                None => Span::styled(
+                    "xx".repeat(block_size),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            };
+            let plainblock = match i.checked_sub(1) {
+                Some(j) => Span::styled(hex::encode(plainblocks[j]), STYLE_KNOWN()),
+                None => Span::raw("iv"),
+            };
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{i}. ")),
+                cipherblock,
+                Span::raw(" -> "),
+                plainblock,
+            ]))
+        })
+        .collect::<Vec<_>>();
+
+    let list = List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Forging: {:?}", machine.state))
+            .title_alignment(Alignment::Center),
+    );
+
+    f.render_widget(list, panel);
+}
+
+fn render_forging_computations<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &ForgingMachine<O>,
+    panel: Rect,
+) {
+    let block_size = machine.block_size();
+    let num = machine.block_num.unwrap_or_default();
+    let plainblock = &machine.plaintext[num * block_size..(num + 1) * block_size];
+    let previous = machine
+        .intermediate
+        .iter()
+        .zip(plainblock)
+        .map(|(i, p)| i ^ p)
+        .collect::<Vec<_>>();
+
+    let paragraph = Paragraph::new(Spans::from(vec![
+        Span::styled(
+            format!("{}(previous)", hex::encode(previous)),
+            STYLE_CIPHERTEXT(),
+        ),
+        Span::raw(" = "),
+        Span::styled(
+            format!("{}(decrypted)", hex::encode(&machine.intermediate)),
+            STYLE_COUNTER(),
+        ),
+        Span::raw(" xor "),
+        Span::styled(format!("{}(plain)", hex::encode(plainblock)), STYLE_KNOWN()),
+    ]))
+    .alignment(Alignment::Center)
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Previous block")
+            .title_alignment(Alignment::Center),
+    );
+
+    f.render_widget(paragraph, panel);
+}
+
+fn render_forgery_panel<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &ForgingMachine<O>,
+    panel: Rect,
+) {
+    let mut lines = vec![Spans::from(vec![
+        Span::raw("plaintext:  "),
+        Span::styled(String::from_utf8_lossy(&machine.message), STYLE_KNOWN()),
+    ])];
+
+    if let Some((iv, ciphertext)) = machine.forgery() {
+        lines.push(Spans::from(vec![
+            Span::raw("iv:         "),
+            Span::styled(hex::encode(iv), STYLE_IV()),
+        ]));
+        lines.push(Spans::from(vec![
+            Span::raw("ciphertext: "),
+            Span::styled(hex::encode(ciphertext), STYLE_CIPHERTEXT()),
+        ]));
+    }
+
+    match &machine.verified {
+        Some(Some(decrypted)) if *decrypted == machine.message => {
+            lines.push(Spans::from(Span::styled(
+                "✓ the key decrypts it to the plaintext",
+                Style::default().fg(Color::Green),
+            )))
+        }
+        Some(_) => lines.push(Spans::from(Span::styled(
+            "X the key doesn't decrypt it to the plaintext",
+            Style::default().fg(Color::Red),
+        ))),
+        None => {}
     }
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Forgery")
+            .title_alignment(Alignment::Center),
+    );
+
+    f.render_widget(paragraph, panel);
 }
 
-fn render_ciphertext_panel<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachine, panel: Rect) {
-    let cipherblocks = [&machine.oracle.iv()[..], &machine.ciphertext].concat();
+fn render_ciphertext_panel<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    panel: Rect,
+) {
+    let cipherblocks = [&machine.initial_iv[..], &machine.ciphertext].concat();
 
     // Blocks are numerated from 0, but since we also add iv, all cipherblock
     // indexes should start with a one
@@ -79,7 +491,7 @@ fn render_ciphertext_panel<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMac
     let block_num = machine.block_num.map(|i| i + 1);
 
     let cipherblocks = cipherblocks
-        .chunks(BLOCK_SIZE)
+        .chunks(machine.block_size)
         .enumerate()
         .map(|(i, block)| (i, format!("{}. {}  ", i, hex::encode(block))))
         .map(|(i, block)| match i {
@@ -100,7 +512,11 @@ fn render_ciphertext_panel<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMac
     f.render_widget(cipher_blocks, panel);
 }
 
-fn render_decrypted_panel<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachine, panel: Rect) {
+fn render_decrypted_panel<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    panel: Rect,
+) {
     let decrypted = String::from_utf8_lossy(&machine.decrypted).to_string();
 
     let block = Block::default()
@@ -116,14 +532,19 @@ fn render_decrypted_panel<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMach
     f.render_widget(decrypted_blocks, inner);
 }
 
-fn render_variable_table<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachine, panel: Rect) {
-    let padding = [machine.padding].repeat(machine.padding as usize);
+fn render_variable_table<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    panel: Rect,
+) {
+    let padding = machine.target_padding();
 
-    let block = format_block(&machine.block, STYLE_CIPHERTEXT());
-    let iv = format_block(&machine.iv, STYLE_IV());
-    let known = format_block(&machine.known, STYLE_KNOWN());
-    let padding = format_block(&padding, STYLE_PADDING());
-    let counter = format_block(&machine.counter, STYLE_COUNTER());
+    let block_size = machine.block_size;
+    let block = format_block(&machine.block, block_size, STYLE_CIPHERTEXT());
+    let iv = format_block(&machine.iv, block_size, STYLE_IV());
+    let known = format_block(&machine.known, block_size, STYLE_KNOWN());
+    let padding = format_block(&padding, block_size, STYLE_PADDING());
+    let counter = format_block(&machine.counter, block_size, STYLE_COUNTER());
 
     let rows = [
         Row::new([Cell::from("cipherblock"), Cell::from(block)]),
@@ -134,7 +555,7 @@ fn render_variable_table<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachi
     ];
 
     let table = Table::new(rows)
-        .widths(&[Constraint::Percentage(35), Constraint::Percentage(65)])
+        .widths(&[Constraint::Length(VARIABLE_NAME_WIDTH), Constraint::Min(0)])
         .block(
             Block::default()
                 .borders(Borders::ALL)
@@ -145,7 +566,11 @@ fn render_variable_table<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachi
     f.render_widget(table, panel);
 }
 
-fn render_oracle_panel<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachine, panel: Rect) {
+fn render_oracle_panel<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    panel: Rect,
+) {
     let block = Block::default()
         .style(Style::default())
         .borders(Borders::ALL)
@@ -178,7 +603,11 @@ fn render_oracle_panel<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachine
     f.render_widget(paragrah, panel);
 }
 
-fn render_computations<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachine, panel: Rect) {
+fn render_computations<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    panel: Rect,
+) {
     let block = Block::default().borders(Borders::ALL);
     if machine.state != State::CalculatingPlainByte {
         f.render_widget(block, panel);
@@ -189,10 +618,10 @@ fn render_computations<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachine
         .title("Plaintext byte")
         .title_alignment(Alignment::Center);
 
-    let idx = BLOCK_SIZE - machine.padding as usize;
+    let idx = machine.block_size - machine.padding as usize;
     let iv = machine.iv[idx];
     let counter = machine.counter[idx];
-    let padding = machine.padding;
+    let padding = machine.target_padding()[0];
     let plain = iv ^ counter ^ padding;
 
     let paragraph = Paragraph::new(Spans::from(vec![
@@ -210,11 +639,34 @@ fn render_computations<B: Backend>(f: &mut Frame<B>, machine: &DecryptingMachine
     f.render_widget(paragraph, panel);
 }
 
-fn render_footer<B: Backend>(
+fn render_explanation<B: Backend, O: PaddingOracle>(
+    f: &mut Frame<B>,
+    machine: &DecryptingMachine<O>,
+    panel: Rect,
+) {
+    let lines = explain(machine)
+        .into_iter()
+        .map(Spans::from)
+        .collect::<Vec<_>>();
+
+    let paragraph = Paragraph::new(lines)
+        .wrap(tui::widgets::Wrap { trim: true })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("What's going on")
+                .title_alignment(Alignment::Center),
+        );
+
+    f.render_widget(paragraph, panel);
+}
+
+fn render_footer<B: Backend, O: PaddingOracle>(
     f: &mut Frame<B>,
-    machine: &DecryptingMachine,
+    machine: &DecryptingMachine<O>,
     timeout: Duration,
     advance: bool,
+    status: Option<&Status>,
     panel: Rect,
 ) {
     let cycles = machine.oracle.counter();
@@ -224,10 +676,20 @@ fn render_footer<B: Backend>(
         .alignment(Alignment::Right);
 
     f.render_widget(paragraph, panel);
+
+    // On top of the same line, the counters are on the right anyway
+    let status = match status {
+        Some(Status::Info(text)) => Span::raw(format!(" {text}")),
+        Some(Status::Error(text)) => {
+            Span::styled(format!(" {text}"), Style::default().fg(Color::LightRed))
+        }
+        None => return,
+    };
+    f.render_widget(Paragraph::new(Spans::from(status)), panel);
 }
 
-fn format_block(data: &[u8], style: Style) -> Spans {
-    let padding = BLOCK_SIZE.saturating_sub(data.len());
+fn format_block(data: &[u8], block_size: usize, style: Style) -> Spans<'static> {
+    let padding = block_size.saturating_sub(data.len());
     let mut spans = vec![];
 
     let mut delim = "";
@@ -249,6 +711,63 @@ fn format_block(data: &[u8], style: Style) -> Spans {
     Spans::from(spans)
 }
 
+fn render_screen_too_small<B: Backend>(f: &mut Frame<B>, screen: Rect, width: u16, height: u16) {
+    let block = Block::default()
+        .borders(Borders::all())
+        .style(Style::default().bg(Color::Blue));
+    f.render_widget(block, screen);
+
+    let text = format!(
+        "Sorry, your screen is too small\n{}x{} is needed, it's {}x{}",
+        width, height, screen.width, screen.height
+    );
+    let center = centered_rect(screen.width, 2, screen);
+    let paragraph = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .wrap(tui::widgets::Wrap { trim: true });
+    f.render_widget(paragraph, center);
+}
+
+/// Borders, the variable names and a block of bytes
+fn min_width(block_size: usize) -> u16 {
+    2 + VARIABLE_NAME_WIDTH + 1 + 3 * block_size as u16 - 1
+}
+
+fn is_compact(screen: Rect) -> bool {
+    screen.width < COMPACT_WIDTH || screen.height < COMPACT_HEIGHT
+}
+
+/// `error` is set if the oracle didn't answer at all
+fn render_failed<B: Backend>(f: &mut Frame<B>, error: Option<String>, screen: Rect) {
+    const FAILED: [&str; 2] = [
+        "The oracle rejected all 256 guesses.",
+        "There is no padding to hit, the attack is stopped.",
+    ];
+    let lines = match &error {
+        Some(err) => vec!["The oracle didn't answer:", err, "The attack is stopped."],
+        None => FAILED.to_vec(),
+    };
+
+    // Border + lines + border
+    let height = lines.len() as u16 + 2;
+    // Border + whitespace + text + whitespace + border
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as u16 + 4;
+
+    let area = centered_rect(width, height, screen);
+
+    let paragrah = Paragraph::new(lines.join("\n"))
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Failed")
+                .style(Style::default().bg(Color::Red)),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(paragrah, area);
+}
+
 fn render_press_any_key<B: Backend>(f: &mut Frame<B>, screen: Rect) {
     const PRESS_ANY_KEY: &str = "Press c to continue";
 
@@ -293,3 +812,52 @@ fn centered_rect(size_x: u16, size_y: u16, r: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1]
 }
+
+#[test]
+fn test_screen_sizes() {
+    use crate::machine::test_machine;
+    use crate::oracle::{Cipher, PaddingScheme};
+    use tui::{backend::TestBackend, Terminal};
+
+    let mut machine = test_machine(
+        b"Hello Internet people",
+        PaddingScheme::Pkcs7,
+        Cipher::Aes128,
+    );
+    machine.advance();
+
+    for (width, height) in [(0, 0), (40, 10), (70, 17), (80, 24), (200, 60)] {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|f| ui(f, &machine, Duration::ZERO, false, None))
+            .unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let text = buffer
+            .content
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect::<String>();
+        // Nothing fits on an empty screen, it only must not panic
+        let too_small = width < 70 || height < MIN_HEIGHT;
+        if width > 0 {
+            assert_eq!(text.contains("too small"), too_small, "{width}x{height}");
+        }
+        if !too_small {
+            assert!(text.contains("counter"), "{width}x{height}");
+        }
+    }
+
+    // A failed export is shown in the footer
+    let status = Status::Error("can't write /report.md".into());
+    let mut terminal = Terminal::new(TestBackend::new(200, 60)).unwrap();
+    terminal
+        .draw(|f| ui(f, &machine, Duration::ZERO, false, Some(&status)))
+        .unwrap();
+    let last_line = terminal.backend().buffer().content[200 * 59..]
+        .iter()
+        .map(|cell| cell.symbol.as_str())
+        .collect::<String>();
+    assert!(last_line.contains("can't write /report.md"), "{last_line}");
+    assert!(last_line.contains("queries"), "{last_line}");
+}
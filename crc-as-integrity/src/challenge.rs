@@ -0,0 +1,217 @@
+use rand::Rng;
+
+use crate::checksum::Checksum;
+use crate::packet::{Format, Mode};
+
+const EXPECTED_CMD: &str = "detonate";
+const DONT_ROLL_YOUR_CRYPTO: &str = "don't roll your own crypto";
+
+/// What the attacker intercepts, and what the server has to get instead
+pub struct Challenge {
+    /// Plaintexts of the intercepted packets
+    pub intercepted: Vec<Vec<u8>>,
+    /// The same plaintexts as the attacker knows them, unknown bytes are `?`
+    pub known: Vec<String>,
+    /// Plaintext, that makes the server explode
+    pub expected: Vec<u8>,
+    /// The same plaintext as the attacker knows it
+    pub task: String,
+    /// What the server answers to the intercepted packets
+    pub reply: &'static str,
+    /// Packets start with a big-endian length of the command
+    pub header: bool,
+}
+
+/// What the server does with a packet, once it's decrypted
+#[derive(Debug, PartialEq, Eq)]
+pub enum Answer {
+    Explode,
+    Reply(&'static str),
+}
+
+impl Challenge {
+    /// The original one: forge a shorter command out of a known one
+    fn shorter(intercepted: &str) -> Self {
+        Self {
+            intercepted: vec![intercepted.into()],
+            known: vec![intercepted.into()],
+            expected: EXPECTED_CMD.into(),
+            task: EXPECTED_CMD.into(),
+            reply: "why not? :)",
+            header: false,
+        }
+    }
+
+    /// The target is longer than the intercepted command, but another packet
+    /// with a known plaintext was encrypted with the same keystream
+    fn longer() -> Self {
+        const BANNER: &str = "hello, I'm device 7, firmware 1.2.3, everything is fine";
+        const TARGET: &str = "detonate and don't ask questions";
+        Self {
+            intercepted: vec!["ping".into(), BANNER.into()],
+            known: vec!["ping".into(), BANNER.into()],
+            expected: TARGET.into(),
+            task: TARGET.into(),
+            reply: "pong",
+            header: false,
+        }
+    }
+
+    /// A part of the plaintext is a random token. The checksum is still
+    /// computed over the ciphertext, or fixed with a delta, if it's encrypted
+    fn token() -> Self {
+        let token = hex::encode(rand::thread_rng().gen::<[u8; 4]>());
+        let unknown = "?".repeat(token.len());
+        Self {
+            intercepted: vec![format!("cmd=selftest;token={token}").into_bytes()],
+            known: vec![format!("cmd=selftest;token={unknown}")],
+            expected: format!("cmd={EXPECTED_CMD};token={token}").into_bytes(),
+            task: format!("cmd={EXPECTED_CMD};token={unknown}"),
+            reply: "self-test passed",
+            header: false,
+        }
+    }
+
+    /// The length of the command is encrypted in front of it
+    fn header() -> Self {
+        let with_header =
+            |cmd: &str| [&(cmd.len() as u16).to_be_bytes()[..], cmd.as_bytes()].concat();
+        let known = |cmd: &str| {
+            let [hi, lo] = (cmd.len() as u16).to_be_bytes();
+            format!("\\x{hi:02x}\\x{lo:02x}{cmd}")
+        };
+        Self {
+            intercepted: vec![with_header(DONT_ROLL_YOUR_CRYPTO)],
+            known: vec![known(DONT_ROLL_YOUR_CRYPTO)],
+            expected: with_header(EXPECTED_CMD),
+            task: known(EXPECTED_CMD),
+            reply: "why not? :)",
+            header: true,
+        }
+    }
+
+    pub fn answer(&self, plaintext: &[u8]) -> anyhow::Result<Answer> {
+        if self.header {
+            if plaintext.len() < 2 {
+                anyhow::bail!("no header");
+            }
+            let (len, cmd) = plaintext.split_at(2);
+            let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+            if len != cmd.len() {
+                anyhow::bail!(
+                    "the header says {len} bytes, but the command is {} bytes long",
+                    cmd.len()
+                );
+            }
+        }
+
+        if plaintext == self.expected {
+            Ok(Answer::Explode)
+        } else if self.intercepted.iter().any(|p| p == plaintext) {
+            Ok(Answer::Reply(self.reply))
+        } else {
+            anyhow::bail!("don't know what are you talking about")
+        }
+    }
+}
+
+/// One step of the campaign
+pub struct Level {
+    pub name: &'static str,
+    /// What makes it harder than the previous one
+    pub description: &'static str,
+    pub format: Format,
+    challenge: fn() -> Challenge,
+}
+
+impl Level {
+    /// A new challenge every time, so random tokens are different
+    pub fn challenge(&self) -> Challenge {
+        (self.challenge)()
+    }
+}
+
+const fn crc(checksum: Checksum) -> Format {
+    Format {
+        mode: Mode::Crc,
+        checksum,
+    }
+}
+
+pub const LEVELS: [Level; 6] = [
+    Level {
+        name: "Shorter command",
+        description: "Forge a command, that is shorter than the intercepted one",
+        format: crc(Checksum::Crc16),
+        challenge: || Challenge::shorter(DONT_ROLL_YOUR_CRYPTO),
+    },
+    Level {
+        name: "Longer command",
+        description: "The command is longer than the intercepted one, but every packet \
+                      is encrypted with the same keystream",
+        format: crc(Checksum::Crc8),
+        challenge: Challenge::longer,
+    },
+    Level {
+        name: "Unknown token",
+        description: "A part of the plaintext is unknown, it has to stay as it is",
+        format: crc(Checksum::Crc32),
+        challenge: Challenge::token,
+    },
+    Level {
+        name: "Length header",
+        description: "The server checks the length of the command in the encrypted header",
+        format: crc(Checksum::Crc16Xmodem),
+        challenge: Challenge::header,
+    },
+    Level {
+        name: "Wide checksum",
+        description: "64 bits of checksum, surely it's enough",
+        format: crc(Checksum::Crc64),
+        challenge: || Challenge::shorter("64 bits is a lot of checksum"),
+    },
+    Level {
+        name: "Encrypted checksum",
+        description: "The checksum of the plaintext is encrypted with it, like in WEP",
+        format: Format {
+            mode: Mode::EncryptedCrc,
+            checksum: Checksum::Crc32,
+        },
+        challenge: Challenge::token,
+    },
+];
+
+/// Every level can be solved with `forge`
+#[test]
+fn test_levels() {
+    use crate::packet::{forge_packet, open_packet, seal_packet, Keys};
+
+    // Only what the attacker knows: everything before the first `?`
+    fn known_part<'a>(text: &str, bytes: &'a [u8]) -> &'a [u8] {
+        &bytes[..text.find('?').unwrap_or(bytes.len())]
+    }
+
+    let keys = Keys::random();
+    for level in &LEVELS {
+        let challenge = level.challenge();
+        // The last intercepted packet is the longest one
+        let num = challenge.intercepted.len() - 1;
+        let sealed = seal_packet(&challenge.intercepted[num], &keys, level.format);
+        let opened = open_packet(&sealed, &keys, level.format).unwrap();
+        assert_eq!(
+            challenge.answer(&opened).unwrap(),
+            Answer::Reply(challenge.reply)
+        );
+
+        let known = known_part(&challenge.known[num], &challenge.intercepted[num]);
+        let target = known_part(&challenge.task, &challenge.expected);
+        let forged = forge_packet(&sealed, known, target, level.format).unwrap();
+        let opened = open_packet(&forged, &keys, level.format).unwrap();
+        assert_eq!(
+            challenge.answer(&opened).unwrap(),
+            Answer::Explode,
+            "{}",
+            level.name
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crc::Crc;
+
+const CRC_8: Crc<u8> = Crc::<u8>::new(&crc::CRC_8_SMBUS);
+const CRC_16: Crc<u16> = Crc::<u16>::new(&crc::CRC_16_IBM_SDLC);
+const CRC_16_ARC: Crc<u16> = Crc::<u16>::new(&crc::CRC_16_ARC);
+const CRC_16_XMODEM: Crc<u16> = Crc::<u16>::new(&crc::CRC_16_XMODEM);
+const CRC_32: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+const CRC_64: Crc<u64> = Crc::<u64>::new(&crc::CRC_64_XZ);
+
+/// Digest appended after the packet. None of them has a key, so anyone who
+/// can change the packet can compute a new digest as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ArgEnum)]
+pub enum Checksum {
+    /// CRC-8/SMBUS
+    Crc8,
+    /// CRC-16/IBM-SDLC, also known as CRC-16/X-25
+    Crc16,
+    /// CRC-16/ARC
+    Crc16Arc,
+    /// CRC-16/XMODEM
+    Crc16Xmodem,
+    /// CRC-32/ISO-HDLC, the one from Ethernet and zip
+    Crc32,
+    /// CRC-64/XZ
+    Crc64,
+    /// Adler-32 from zlib
+    Adler32,
+    /// 64-bit xxHash with a zero seed
+    Xxhash64,
+}
+
+impl Checksum {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Crc8 => "CRC-8",
+            Self::Crc16 => "CRC-16",
+            Self::Crc16Arc => "CRC-16/ARC",
+            Self::Crc16Xmodem => "CRC-16/XMODEM",
+            Self::Crc32 => "CRC-32",
+            Self::Crc64 => "CRC-64",
+            Self::Adler32 => "Adler-32",
+            Self::Xxhash64 => "xxHash64",
+        }
+    }
+
+    /// Size of the digest in bytes
+    pub fn size(self) -> usize {
+        match self {
+            Self::Crc8 => 1,
+            Self::Crc16 | Self::Crc16Arc | Self::Crc16Xmodem => 2,
+            Self::Crc32 | Self::Adler32 => 4,
+            Self::Crc64 | Self::Xxhash64 => 8,
+        }
+    }
+
+    /// Digest of the data in big-endian
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Crc8 => CRC_8.checksum(data).to_be_bytes().to_vec(),
+            Self::Crc16 => CRC_16.checksum(data).to_be_bytes().to_vec(),
+            Self::Crc16Arc => CRC_16_ARC.checksum(data).to_be_bytes().to_vec(),
+            Self::Crc16Xmodem => CRC_16_XMODEM.checksum(data).to_be_bytes().to_vec(),
+            Self::Crc32 => CRC_32.checksum(data).to_be_bytes().to_vec(),
+            Self::Crc64 => CRC_64.checksum(data).to_be_bytes().to_vec(),
+            Self::Adler32 => adler::adler32_slice(data).to_be_bytes().to_vec(),
+            Self::Xxhash64 => xxhash_rust::xxh64::xxh64(data, 0).to_be_bytes().to_vec(),
+        }
+    }
+
+    /// CRCs are affine: for data of the same length
+    /// `crc(a ^ b) = crc(a) ^ crc(b) ^ crc(zeroes)`, whatever the initial
+    /// value and the final xor are
+    pub fn is_affine(self) -> bool {
+        !matches!(self, Self::Adler32 | Self::Xxhash64)
+    }
+
+    /// How the digest changes, when the data is xored with `delta` of the
+    /// same length. The data itself is not needed, so it works even if the
+    /// digest is encrypted. None, if the checksum is not affine
+    pub fn delta(self, delta: &[u8]) -> Option<Vec<u8>> {
+        if !self.is_affine() {
+            return None;
+        }
+
+        let zeroes = vec![0; delta.len()];
+        let mut digest = self.digest(delta);
+        digest
+            .iter_mut()
+            .zip(self.digest(&zeroes))
+            .for_each(|(d, z)| *d ^= z);
+        Some(digest)
+    }
+}
+
+#[test]
+fn test_digest_sizes() {
+    use clap::ArgEnum;
+
+    for checksum in Checksum::value_variants() {
+        assert_eq!(checksum.digest(b"detonate").len(), checksum.size());
+    }
+
+    let data = b"don't roll your own crypto";
+    let delta = b"\x00\x0a\x1a\x48\x1a\x41\x06\x0a and a few more bytes";
+    let changed = data
+        .iter()
+        .zip(delta)
+        .map(|(d, x)| d ^ x)
+        .collect::<Vec<_>>();
+    for checksum in [Checksum::Crc8, Checksum::Crc16Arc, Checksum::Crc64] {
+        let mut digest = checksum.digest(data);
+        let diff = checksum.delta(&delta[..data.len()]).unwrap();
+        digest.iter_mut().zip(diff).for_each(|(d, x)| *d ^= x);
+        assert_eq!(digest, checksum.digest(&changed));
+    }
+    assert_eq!(Checksum::Adler32.delta(delta), None);
+    // The check value from the CRC catalogue
+    assert_eq!(
+        Checksum::Crc32.digest(b"123456789"),
+        [0xcb, 0xf4, 0x39, 0x26]
+    );
+}
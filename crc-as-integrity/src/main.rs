@@ -1,19 +1,13 @@
+use std::collections::BTreeSet;
 use std::fmt::Display;
 
-use aes::cipher::{KeyIvInit, StreamCipher};
+use challenge::{Answer, Challenge, Level, LEVELS};
+use checksum::Checksum;
 use clap::Parser;
-use crc::Crc;
-use rand::Rng;
+use packet::{decrypt, forge_packet, open_packet, seal_packet, Format, Keys, Mode};
 use shell::Shell;
 use yansi::Paint;
 
-type Aes128Ctr64LE = ctr::Ctr64LE<aes::Aes128>;
-const CRC_ALGO: Crc<u16> = Crc::<u16>::new(&crc::CRC_16_IBM_SDLC);
-const CRC_LEN: usize = 2;
-
-const EXPECTED_CMD: &str = "detonate";
-const DONT_ROLL_YOUR_CRYPTO: &str = "don't roll your own crypto";
-
 const EXPLOSION: &str = r#"
            _.-^^---....,,--
        _--                  --_
@@ -41,49 +35,29 @@ impl std::str::FromStr for HexString {
     }
 }
 
+mod challenge;
+mod checksum;
+mod packet;
 mod shell;
 
-fn encrypt(data: &mut [u8], key: &[u8], iv: &[u8]) {
-    let mut cipher = Aes128Ctr64LE::new(key.into(), iv.into());
-    cipher.apply_keystream(data);
-}
-
-fn decrypt(data: &mut [u8], key: &[u8], iv: &[u8]) {
-    // hehe
-    encrypt(data, key, iv)
-}
-
-fn crc(data: &[u8]) -> [u8; CRC_LEN] {
-    CRC_ALGO.checksum(data).to_be_bytes()
-}
-
-fn seal_packet(data: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
-    let mut data = data.to_owned();
-    encrypt(&mut data, key, iv);
-    let crc = crc(&data);
-    data.extend_from_slice(&crc);
-    data
-}
-
-fn open_packet(data: &[u8], key: &[u8], iv: &[u8]) -> anyhow::Result<Vec<u8>> {
-    if data.len() < CRC_LEN {
-        anyhow::bail!("length is too small");
-    }
-
-    let (data, crc_bytes) = data.split_at(data.len() - CRC_LEN);
-    if crc_bytes != crc(data) {
-        anyhow::bail!("decryption error");
-    }
-
-    let mut data = data.to_owned();
-    decrypt(&mut data, key, iv);
-    Ok(data)
-}
-
 fn xor(a: &mut [u8], b: &[u8]) {
     a.iter_mut().zip(b).for_each(|(a, b)| *a ^= *b);
 }
 
+/// Options of the demo itself, the shell has its own commands
+#[derive(Parser, Debug)]
+struct Args {
+    /// Level to start from, see `levels` in the shell
+    #[clap(long, default_value_t = 1)]
+    level: usize,
+    /// How packets are protected on every level. Only the CRC modes can be forged
+    #[clap(long, arg_enum)]
+    mode: Option<Mode>,
+    /// Checksum appended to every packet on every level in the CRC modes
+    #[clap(long, arg_enum)]
+    checksum: Option<Checksum>,
+}
+
 #[derive(Parser, Debug)]
 #[clap(about = None, long_about = None)]
 enum Commands {
@@ -99,11 +73,25 @@ enum Commands {
         /// Hex-encoded string
         hex: HexString,
     },
-    /// Calculate crc on a hex-string
+    /// Calculate the checksum on a hex-string
     Crc {
         /// Hex-encoded string
         hex: HexString,
     },
+    /// Calculate how the checksum changes, when the data is xored with a delta
+    Crcdiff {
+        /// Hex-encoded delta as long as the data, with zeroes where nothing changes
+        delta: HexString,
+    },
+    /// Forge a packet with another command out of the intercepted one
+    Forge {
+        /// Hex-encoded intercepted packet
+        packet: HexString,
+        /// Command in the intercepted packet, `\xNN` for binary bytes
+        known: String,
+        /// Command for the forged packet, `\xNN` for binary bytes
+        target: String,
+    },
     /// Convert string into hex
     Hex {
         /// utf8 string
@@ -112,15 +100,83 @@ enum Commands {
 
     /// Print intercepted packet
     Intercept,
+    /// List levels and the progress
+    Levels,
+    /// Switch to another level
+    Level {
+        /// Number of the level, starting from 1
+        num: usize,
+    },
 }
 
-fn main() -> anyhow::Result<()> {
-    let key: [u8; 16] = rand::thread_rng().gen();
-    let iv: [u8; 16] = rand::thread_rng().gen();
+/// The current level and what's intercepted on it. Keys are new on every
+/// level, so packets from one level can't be replayed on another
+struct Game {
+    mode: Option<Mode>,
+    checksum: Option<Checksum>,
+    level: usize,
+    completed: BTreeSet<usize>,
+    format: Format,
+    keys: Keys,
+    challenge: Challenge,
+    intercepted: Vec<Vec<u8>>,
+}
+
+impl Game {
+    fn new(level: usize, mode: Option<Mode>, checksum: Option<Checksum>) -> anyhow::Result<Self> {
+        let mut game = Self {
+            mode,
+            checksum,
+            level: 0,
+            completed: BTreeSet::new(),
+            format: LEVELS[0].format,
+            keys: Keys::random(),
+            challenge: LEVELS[0].challenge(),
+            intercepted: vec![],
+        };
+        game.start(level)?;
+        Ok(game)
+    }
 
-    let intercepted = seal_packet(DONT_ROLL_YOUR_CRYPTO.as_bytes(), &key, &iv);
+    /// `num` starts from 1, as in the `levels` list
+    fn start(&mut self, num: usize) -> anyhow::Result<()> {
+        if num == 0 || num > LEVELS.len() {
+            anyhow::bail!("there are levels from 1 to {}", LEVELS.len());
+        }
 
-    print_greetings(&intercepted);
+        self.level = num - 1;
+        self.format = self.format_of(num - 1);
+        self.keys = Keys::random();
+        self.challenge = LEVELS[self.level].challenge();
+        // Every packet is encrypted with the same key and iv
+        self.intercepted = self
+            .challenge
+            .intercepted
+            .iter()
+            .map(|data| seal_packet(data, &self.keys, self.format))
+            .collect();
+        Ok(())
+    }
+
+    /// Format of the level with `--mode` and `--checksum` applied
+    fn format_of(&self, level: usize) -> Format {
+        let format = LEVELS[level].format;
+        Format {
+            mode: self.mode.unwrap_or(format.mode),
+            checksum: self.checksum.unwrap_or(format.checksum),
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let Args {
+        level,
+        mode,
+        checksum,
+    } = Args::parse();
+    let mut game = Game::new(level, mode, checksum)?;
+
+    print_greetings(&game);
 
     let shell = Shell::<Commands>::new("~> ");
 
@@ -132,12 +188,29 @@ fn main() -> anyhow::Result<()> {
             } => xor_cmd(a, b),
             Commands::Send {
                 hex: HexString(data),
-            } => send_cmd(data, &key, &iv)?,
+            } => send_cmd(data, &mut game)?,
             Commands::Crc {
                 hex: HexString(data),
-            } => println!("{}", hex::encode(crc(&data))),
+            } => crc_cmd(&data, game.format),
+            Commands::Crcdiff {
+                delta: HexString(delta),
+            } => crcdiff_cmd(&delta, game.format.checksum)?,
+            Commands::Forge {
+                packet: HexString(packet),
+                known,
+                target,
+            } => {
+                let (known, target) = (unescape(&known)?, unescape(&target)?);
+                let forged = forge_packet(&packet, &known, &target, game.format)?;
+                println!("{}", Sealed(&forged, game.format));
+            }
             Commands::Hex { string } => println!("{}", hex::encode(string)),
-            Commands::Intercept => print_intercepted(&intercepted),
+            Commands::Intercept => print_intercepted(&game),
+            Commands::Levels => levels_cmd(&game),
+            Commands::Level { num } => {
+                game.start(num)?;
+                print_greetings(&game);
+            }
         }
         Ok(())
     })?;
@@ -145,31 +218,151 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn print_intercepted(intercepted: &[u8]) {
-    println!(
-        "Congratulations! You've intercepted a packet with command {:?}",
-        Paint::green(DONT_ROLL_YOUR_CRYPTO)
-    );
-    println!("The packet is encrypted in CTR mode with 16-bit crc check appended");
-    println!("after the packet:");
+fn print_intercepted(game: &Game) {
+    let Game {
+        format,
+        challenge,
+        intercepted,
+        ..
+    } = game;
+    if let [known] = &challenge.known[..] {
+        println!(
+            "Congratulations! You've intercepted a packet with command \"{}\"",
+            Paint::green(known)
+        );
+    } else {
+        println!(
+            "Congratulations! You've intercepted {} packets with commands",
+            intercepted.len()
+        );
+        for known in &challenge.known {
+            println!("  \"{}\"", Paint::green(known));
+        }
+    }
+    if challenge.header {
+        println!("Every command starts with its length as a 2-byte big-endian header.");
+    }
+    // With the same key and iv, as if the devices never change them
+    let packet = if intercepted.len() == 1 {
+        "The packet is"
+    } else {
+        "Every packet is"
+    };
+    match format.mode {
+        Mode::Crc | Mode::Hmac => {
+            println!(
+                "{packet} encrypted in CTR mode with {} appended",
+                format.tag_name()
+            );
+            println!("after the packet:");
+        }
+        Mode::EncryptedCrc => {
+            println!(
+                "{packet} encrypted in CTR mode together with {} of",
+                format.tag_name()
+            );
+            println!("the plaintext at the end:");
+        }
+        Mode::Gcm => {
+            println!(
+                "{packet} encrypted in GCM mode with a new {}-bit nonce in front",
+                format.nonce_size() * 8
+            );
+            println!("and {} appended after the packet:", format.tag_name());
+        }
+    }
     println!();
-    println!("  {}", Sealed(intercepted));
+    for packet in intercepted {
+        println!("  {}", Sealed(packet, *format));
+    }
     println!();
 }
 
-fn send_cmd(data: Vec<u8>, key: &[u8], iv: &[u8]) -> anyhow::Result<()> {
-    let command = open_packet(&data, key, iv)?;
-    match command {
-        cmd if cmd == EXPECTED_CMD.as_bytes() => {
+fn send_cmd(data: Vec<u8>, game: &mut Game) -> anyhow::Result<()> {
+    let command = open_packet(&data, &game.keys, game.format)?;
+    match game.challenge.answer(&command)? {
+        Answer::Explode => {
             print_secret();
+            game.completed.insert(game.level);
+            let num = game.level + 1;
+            if game.completed.len() == LEVELS.len() {
+                println!("{}", Paint::yellow("All levels are completed!").bold());
+            } else if num < LEVELS.len() {
+                println!(
+                    "Level {num} is completed, type `level {}` for the next one.",
+                    num + 1
+                );
+            } else {
+                println!("Level {num} is completed, type `levels` for the ones left.");
+            }
         }
-        cmd if cmd == DONT_ROLL_YOUR_CRYPTO.as_bytes() => {
-            println!("why not? :)");
-        }
-        _ => {
-            anyhow::bail!("don't know what are you talking about");
+        Answer::Reply(reply) => println!("{reply}"),
+    }
+    Ok(())
+}
+
+fn levels_cmd(game: &Game) {
+    println!();
+    for (level, Level { name, .. }) in LEVELS.iter().enumerate() {
+        let mark = match level {
+            _ if game.completed.contains(&level) => Paint::green("✓"),
+            _ if level == game.level => Paint::yellow(">"),
+            _ => Paint::new(" "),
+        };
+        println!(
+            "  {mark} {}. {name:<20} {}",
+            level + 1,
+            game.format_of(level).tag_name()
+        );
+    }
+    println!();
+    println!("  {} of {} completed", game.completed.len(), LEVELS.len());
+    println!();
+}
+
+/// Bytes of a string with `\xNN` escapes, the way known commands are shown
+fn unescape(s: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if let [b'\\', b'x', hi, lo, tail @ ..] = rest {
+            let escaped =
+                hex::decode([*hi, *lo]).map_err(|_| anyhow::anyhow!("invalid escape in {s:?}"))?;
+            bytes.extend(escaped);
+            rest = tail;
+        } else {
+            bytes.push(byte);
+            rest = tail;
         }
     }
+    Ok(bytes)
+}
+
+fn crc_cmd(data: &[u8], format: Format) {
+    println!("{}", hex::encode(format.checksum.digest(data)));
+    let hint = match format.mode {
+        Mode::Crc => return,
+        Mode::EncryptedCrc => "The checksum is encrypted now, fix it with `crcdiff`".to_owned(),
+        Mode::Hmac | Mode::Gcm => format!(
+            "The server expects a {} now, it can't be computed without the key",
+            format.tag_name()
+        ),
+    };
+    println!("{}", Paint::yellow(hint));
+}
+
+fn crcdiff_cmd(delta: &[u8], checksum: Checksum) -> anyhow::Result<()> {
+    let diff = checksum.delta(delta).ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} is not affine, compute it with `crc` instead",
+            checksum.name()
+        )
+    })?;
+
+    println!();
+    println!("  crc(data xor {}) = ", Paint::green(hex::encode(delta)));
+    println!("  crc(data) xor {}", Paint::blue(hex::encode(diff)));
+    println!();
     Ok(())
 }
 
@@ -205,29 +398,57 @@ fn print_secret() {
     println!();
 }
 
-fn print_greetings(intercepted: &[u8]) {
-    print_intercepted(intercepted);
+fn print_greetings(game: &Game) {
+    let Level {
+        name, description, ..
+    } = &LEVELS[game.level];
     println!(
-        "Your next task: forge {:?} command.",
-        Paint::green(EXPECTED_CMD)
+        "{}",
+        Paint::yellow(format!("Level {}/{}: {name}", game.level + 1, LEVELS.len())).bold()
     );
+    println!("{description}.");
+    println!();
+
+    let challenge = &game.challenge;
+    print_intercepted(game);
+    println!(
+        "Your next task: forge \"{}\" command.",
+        Paint::green(&challenge.task)
+    );
+    if challenge.known.iter().any(|known| known.contains('?')) {
+        println!("You don't know the `?` bytes, they have to stay as they are.");
+    }
     println!("Commands");
-    println!("  crc          Calculate crc on a hex-string");
+    println!("  crc          Calculate the checksum on a hex-string");
+    println!(
+        "  crcdiff      Calculate how the checksum changes, when the data is xored with a delta"
+    );
+    println!("  forge        Forge a packet with another command out of the intercepted one");
     println!("  help         Print this message or the help of the given subcommand(s)");
     println!("  hex          Convert string into hex");
     println!("  intercept    Print intercepted packet");
+    println!("  level        Switch to another level");
+    println!("  levels       List levels and the progress");
     println!("  send         Send encrypted command to the server in a hex format");
     println!("  xor          Xor two hex-strings together");
     println!();
 }
 
-struct Sealed<'a>(&'a [u8]);
+struct Sealed<'a>(&'a [u8], Format);
 
 impl<'a> Display for Sealed<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (data, crc) = self.0.split_at(self.0.len() - CRC_LEN);
+        let (nonce, data) = self.0.split_at(self.1.nonce_size());
+        let (data, tag) = data.split_at(data.len() - self.1.tag_size());
+        let nonce = hex::encode(nonce);
         let data = hex::encode(data);
-        let crc = hex::encode(crc);
-        write!(f, "{}{}", Paint::green(data), Paint::blue(crc))
+        let tag = hex::encode(tag);
+        write!(
+            f,
+            "{}{}{}",
+            Paint::yellow(nonce),
+            Paint::green(data),
+            Paint::blue(tag)
+        )
     }
 }
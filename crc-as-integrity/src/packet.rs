@@ -0,0 +1,368 @@
+use aes::cipher::{KeyIvInit, StreamCipher};
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+use crate::checksum::Checksum;
+
+type Aes128Ctr64LE = ctr::Ctr64LE<aes::Aes128>;
+type HmacSha256 = Hmac<Sha256>;
+
+const HMAC_LEN: usize = 32;
+const GCM_TAG_LEN: usize = 16;
+const GCM_NONCE_LEN: usize = 12;
+const DECRYPTION_ERR: &str = "decryption error";
+
+/// How packets are protected
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ArgEnum)]
+pub enum Mode {
+    /// AES-CTR and a checksum of the ciphertext. Anyone can compute it
+    Crc,
+    /// AES-CTR over the plaintext and its checksum, like in WEP
+    EncryptedCrc,
+    /// AES-CTR and HMAC-SHA256 of the ciphertext, encrypt-then-MAC
+    Hmac,
+    /// AES-GCM, encryption and authentication in one
+    Gcm,
+}
+
+/// What is appended after the ciphertext
+#[derive(Clone, Copy, Debug)]
+pub struct Format {
+    pub mode: Mode,
+    /// Only used in the CRC modes, encrypted or not
+    pub checksum: Checksum,
+}
+
+impl Format {
+    /// Size of what is sent in front of the ciphertext in bytes
+    pub fn nonce_size(self) -> usize {
+        match self.mode {
+            Mode::Gcm => GCM_NONCE_LEN,
+            Mode::Crc | Mode::EncryptedCrc | Mode::Hmac => 0,
+        }
+    }
+
+    /// Size of the checksum or the tag in bytes
+    pub fn tag_size(self) -> usize {
+        match self.mode {
+            Mode::Crc | Mode::EncryptedCrc => self.checksum.size(),
+            Mode::Hmac => HMAC_LEN,
+            Mode::Gcm => GCM_TAG_LEN,
+        }
+    }
+
+    pub fn tag_name(self) -> String {
+        let bits = self.tag_size() * 8;
+        match self.mode {
+            Mode::Crc | Mode::EncryptedCrc => format!("{bits}-bit {} check", self.checksum.name()),
+            Mode::Hmac => format!("{bits}-bit HMAC-SHA256 tag"),
+            Mode::Gcm => format!("{bits}-bit GCM tag"),
+        }
+    }
+}
+
+/// Secrets that both devices share and the attacker never sees
+pub struct Keys {
+    pub key: [u8; 16],
+    pub iv: [u8; 16],
+    /// Separate from the encryption key, as it should be
+    pub mac_key: [u8; 32],
+}
+
+impl Keys {
+    pub fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        Self {
+            key: rng.gen(),
+            iv: rng.gen(),
+            mac_key: rng.gen(),
+        }
+    }
+
+    fn hmac(&self) -> HmacSha256 {
+        <HmacSha256 as Mac>::new_from_slice(&self.mac_key).expect("any key size is fine")
+    }
+
+    fn gcm(&self) -> Aes128Gcm {
+        Aes128Gcm::new(&self.key.into())
+    }
+}
+
+pub fn encrypt(data: &mut [u8], key: &[u8], iv: &[u8]) {
+    let mut cipher = Aes128Ctr64LE::new(key.into(), iv.into());
+    cipher.apply_keystream(data);
+}
+
+pub fn decrypt(data: &mut [u8], key: &[u8], iv: &[u8]) {
+    // hehe
+    encrypt(data, key, iv)
+}
+
+pub fn seal_packet(data: &[u8], keys: &Keys, format: Format) -> Vec<u8> {
+    let mut data = data.to_owned();
+    match format.mode {
+        Mode::Crc => {
+            encrypt(&mut data, &keys.key, &keys.iv);
+            let crc = format.checksum.digest(&data);
+            data.extend_from_slice(&crc);
+        }
+        Mode::EncryptedCrc => {
+            let crc = format.checksum.digest(&data);
+            data.extend_from_slice(&crc);
+            encrypt(&mut data, &keys.key, &keys.iv);
+        }
+        Mode::Hmac => {
+            encrypt(&mut data, &keys.key, &keys.iv);
+            let tag = keys.hmac().chain_update(&data).finalize().into_bytes();
+            data.extend_from_slice(&tag);
+        }
+        Mode::Gcm => {
+            // Unlike the CTR modes, GCM gets a new nonce for every packet and
+            // sends it in front. With a repeated nonce, two packets would leak
+            // the keystream and the key of the tag, and tags could be forged
+            let nonce: [u8; GCM_NONCE_LEN] = rand::thread_rng().gen();
+            let ciphertext = keys
+                .gcm()
+                .encrypt(Nonce::from_slice(&nonce), &data[..])
+                .expect("the packet is not that long");
+            data = [&nonce[..], &ciphertext].concat();
+        }
+    }
+    data
+}
+
+pub fn open_packet(data: &[u8], keys: &Keys, format: Format) -> anyhow::Result<Vec<u8>> {
+    if data.len() < format.nonce_size() + format.tag_size() {
+        anyhow::bail!("length is too small");
+    }
+
+    let (ciphertext, tag) = data.split_at(data.len() - format.tag_size());
+    let valid = match format.mode {
+        Mode::Crc => tag == format.checksum.digest(ciphertext),
+        Mode::EncryptedCrc => {
+            let mut data = data.to_owned();
+            decrypt(&mut data, &keys.key, &keys.iv);
+            let (plaintext, crc) = data.split_at(ciphertext.len());
+            if crc != format.checksum.digest(plaintext) {
+                anyhow::bail!(DECRYPTION_ERR);
+            }
+            return Ok(plaintext.to_owned());
+        }
+        Mode::Hmac => keys
+            .hmac()
+            .chain_update(ciphertext)
+            .verify_slice(tag)
+            .is_ok(),
+        Mode::Gcm => {
+            let (nonce, data) = data.split_at(GCM_NONCE_LEN);
+            return keys
+                .gcm()
+                .decrypt(Nonce::from_slice(nonce), data)
+                .map_err(|_| anyhow::anyhow!(DECRYPTION_ERR));
+        }
+    };
+    if !valid {
+        anyhow::bail!(DECRYPTION_ERR);
+    }
+
+    let mut data = ciphertext.to_owned();
+    decrypt(&mut data, &keys.key, &keys.iv);
+    Ok(data)
+}
+
+/// Replaces the known plaintext at the start of an intercepted packet with
+/// the target one, the same as the `xor` and `crc` commands do by hand. The
+/// target can be shorter only if the whole plaintext is known, then the
+/// packet is cut after it. Otherwise the unknown rest of the plaintext
+/// stays, and the target must be as long as the known part it replaces.
+pub fn forge_packet(
+    packet: &[u8],
+    known: &[u8],
+    target: &[u8],
+    format: Format,
+) -> anyhow::Result<Vec<u8>> {
+    if !matches!(format.mode, Mode::Crc | Mode::EncryptedCrc) {
+        anyhow::bail!("a {} can't be forged without the key", format.tag_name());
+    }
+
+    let (data, tag) = packet.split_at(packet.len().saturating_sub(format.tag_size()));
+    if known.len() > data.len() {
+        anyhow::bail!(
+            "the packet has {} encrypted bytes, but the known plaintext is {} bytes long",
+            data.len(),
+            known.len()
+        );
+    }
+    if target.len() > known.len() {
+        anyhow::bail!(
+            "the target is longer than the known plaintext, the rest of the keystream is unknown"
+        );
+    }
+
+    let len = if target.len() == known.len() {
+        data.len()
+    } else if known.len() == data.len() {
+        target.len()
+    } else {
+        anyhow::bail!(
+            "the target can be shorter only if the whole plaintext is known, \
+             otherwise the packet can't be cut after it"
+        );
+    };
+
+    if format.mode == Mode::EncryptedCrc && known.len() == data.len() {
+        // The whole plaintext is known, so its checksum is known too, and
+        // the keystream over both of them. The checksum of the target is
+        // encrypted with the keystream from where the target ends, any
+        // checksum works, affine or not
+        let plaintext = [known, &format.checksum.digest(known)].concat();
+        let forged = [target, &format.checksum.digest(target)].concat();
+        return Ok(forged
+            .iter()
+            .zip(packet.iter().zip(plaintext))
+            .map(|(f, (c, p))| f ^ c ^ p)
+            .collect());
+    }
+
+    // The keystream cancels out: the ciphertext xor the known plaintext is
+    // the keystream, that is xored with the target
+    let mut delta = vec![0; len];
+    for (byte, (k, t)) in delta.iter_mut().zip(known.iter().zip(target)) {
+        *byte = k ^ t;
+    }
+    let mut forged = data[..len].to_vec();
+    forged.iter_mut().zip(&delta).for_each(|(f, d)| *f ^= d);
+
+    if format.mode == Mode::Crc {
+        let crc = format.checksum.digest(&forged);
+        forged.extend_from_slice(&crc);
+        return Ok(forged);
+    }
+
+    // Neither the plaintext nor the checksum is known, but the change of
+    // the checksum only depends on the delta
+    let diff = format.checksum.delta(&delta).ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} is not affine, it can't be fixed without the plaintext",
+            format.checksum.name()
+        )
+    })?;
+    forged.extend(tag.iter().zip(diff).map(|(t, d)| t ^ d));
+    Ok(forged)
+}
+
+/// The attack from the readme: xor the known plaintext out, the wanted one
+/// in, and recompute the checksum. Only the CRC mode lets it through.
+#[test]
+fn test_forgery() {
+    use clap::ArgEnum;
+
+    let keys = Keys::random();
+    let known = b"don't roll your own crypto";
+    let wanted = b"detonate";
+
+    for &mode in Mode::value_variants() {
+        let format = Format {
+            mode,
+            checksum: Checksum::Crc32,
+        };
+        let sealed = seal_packet(known, &keys, format);
+        assert_eq!(open_packet(&sealed, &keys, format).unwrap(), known);
+
+        let mut forged = sealed[..wanted.len()].to_vec();
+        forged
+            .iter_mut()
+            .zip(known.iter().zip(wanted))
+            .for_each(|(c, (k, w))| *c ^= k ^ w);
+        let crc = format.checksum.digest(&forged);
+        forged.extend_from_slice(&crc);
+
+        let opened = open_packet(&forged, &keys, format);
+        let automated = forge_packet(&sealed, known, wanted, format);
+        match mode {
+            Mode::Crc => {
+                assert_eq!(opened.unwrap(), wanted);
+                assert_eq!(automated.unwrap(), forged);
+            }
+            // The checksum over the ciphertext doesn't help, but with the
+            // whole plaintext known the encrypted one can be recomputed
+            Mode::EncryptedCrc => {
+                assert!(opened.is_err());
+                let automated = automated.unwrap();
+                assert_eq!(open_packet(&automated, &keys, format).unwrap(), wanted);
+            }
+            Mode::Hmac | Mode::Gcm => {
+                assert!(opened.is_err());
+                assert!(automated.is_err());
+            }
+        }
+    }
+}
+
+/// A nonce must never repeat under the same key
+#[test]
+fn test_gcm_nonces() {
+    let keys = Keys::random();
+    let format = Format {
+        mode: Mode::Gcm,
+        checksum: Checksum::Crc32,
+    };
+    let first = seal_packet(b"ping", &keys, format);
+    let second = seal_packet(b"ping", &keys, format);
+    assert_ne!(first[..GCM_NONCE_LEN], second[..GCM_NONCE_LEN]);
+    assert_eq!(open_packet(&first, &keys, format).unwrap(), b"ping");
+    assert_eq!(open_packet(&second, &keys, format).unwrap(), b"ping");
+}
+
+/// WEP-like layout: nothing is known about the token, the checksum is fixed
+/// only through linearity
+#[test]
+fn test_encrypted_crc_forgery() {
+    let keys = Keys::random();
+    for checksum in [Checksum::Crc8, Checksum::Crc16, Checksum::Crc64] {
+        let format = Format {
+            mode: Mode::EncryptedCrc,
+            checksum,
+        };
+        let sealed = seal_packet(b"cmd=selftest;token=5eb63bbb", &keys, format);
+
+        let forged = forge_packet(&sealed, b"cmd=selftest", b"cmd=detonate", format).unwrap();
+        let opened = open_packet(&forged, &keys, format).unwrap();
+        assert_eq!(opened, b"cmd=detonate;token=5eb63bbb");
+    }
+}
+
+/// A shorter target cuts the packet, that needs the whole plaintext. With
+/// the whole plaintext any checksum is recomputed, whatever the target length
+#[test]
+fn test_shorter_target() {
+    let keys = Keys::random();
+    let known = b"don't roll your own crypto";
+    for mode in [Mode::Crc, Mode::EncryptedCrc] {
+        // Keyless checksums are recomputed, affine or not
+        for checksum in [
+            Checksum::Crc8,
+            Checksum::Crc64,
+            Checksum::Adler32,
+            Checksum::Xxhash64,
+        ] {
+            let format = Format { mode, checksum };
+            let sealed = seal_packet(known, &keys, format);
+
+            for target in [
+                &b"detonate"[..],
+                b"",
+                b"don't roll your own",
+                b"don't roll your own crypt!",
+            ] {
+                let forged = forge_packet(&sealed, known, target, format).unwrap();
+                assert_eq!(forged.len(), target.len() + checksum.size());
+                assert_eq!(open_packet(&forged, &keys, format).unwrap(), target);
+            }
+            assert!(forge_packet(&sealed, b"don't roll", b"detonate", format).is_err());
+        }
+    }
+}
@@ -62,9 +62,7 @@ impl<P: clap::Parser> Shell<P> {
 fn format_error(err: String) -> String {
     // This is clap's usage, not an error
     if err.starts_with('-') {
-        return err
-            .trim_start_matches(|c| matches!(c, '\n' | '-' | ' '))
-            .to_owned();
+        return err.trim_start_matches(['\n', '-', ' ']).to_owned();
     }
 
     let ascii = err.to_ascii_lowercase();